@@ -0,0 +1,52 @@
+use {crate::run::rendering::scene::SceneSelector, clap::Parser, shader_types::Vec3, std::path::PathBuf, tap::prelude::*};
+
+const DEFAULT_MAP: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets/test-map-1.glb");
+
+#[derive(Debug, Clone, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// `.glb`, `.gltf` or `.obj` file holding the map, files it refers to are resolved next to it
+    #[arg(long, env = "FIRE_AND_SWORD_MAP", default_value = DEFAULT_MAP, value_parser = existing_file)]
+    pub map: PathBuf,
+    /// which scene of the map to load, by index or by name
+    #[arg(long, env = "FIRE_AND_SWORD_SCENE", default_value = "0")]
+    pub scene: SceneSelector,
+    /// where the camera starts, as `x,y,z`
+    #[arg(long, env = "FIRE_AND_SWORD_CAMERA_POSITION", default_value = "0,0,0", value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub camera_position: Vec3,
+    /// in degrees, 0 looks down +x
+    #[arg(long, env = "FIRE_AND_SWORD_CAMERA_YAW", default_value_t = 0., allow_hyphen_values = true)]
+    pub camera_yaw: f32,
+    /// in degrees, positive looks up
+    #[arg(long, env = "FIRE_AND_SWORD_CAMERA_PITCH", default_value_t = 0., allow_hyphen_values = true)]
+    pub camera_pitch: f32,
+}
+
+fn existing_file(path: &str) -> Result<PathBuf, String> {
+    PathBuf::from(path).pipe(|path| match path.is_file() {
+        true => Ok(path),
+        false => Err(format!(
+            "[{}] is not a file, relative paths start at [{}]",
+            path.display(),
+            std::env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_else(|e| format!("unknown working directory: {e}"))
+        )),
+    })
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    s.split(',')
+        .map(|component| {
+            component
+                .trim()
+                .parse::<f32>()
+                .map_err(|e| format!("[{component}]: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|components| {
+            <[f32; 3]>::try_from(components)
+                .map(Vec3::from)
+                .map_err(|components| format!("expected 3 components, got {}", components.len()))
+        })
+}
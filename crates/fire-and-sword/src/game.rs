@@ -1,10 +1,13 @@
 use {
-    crate::run::rendering::{camera::Camera, scene::Scene},
+    crate::run::rendering::{camera::Camera, model::loader::LoadProgress, scene::Scene},
     shader_types::light_source::LightSource,
+    std::sync::Arc,
 };
 
 pub struct GameState {
     pub camera: Camera,
     pub scene: Option<Scene>,
     pub light_sources: Vec<LightSource>,
+    /// while the scene is still being prepared in the background
+    pub loading: Option<Arc<LoadProgress>>,
 }
@@ -1,7 +1,10 @@
 #![allow(clippy::unit_arg)]
 #![feature(new_range_api)]
 
-use anyhow::{Context, Result};
+use {
+    anyhow::{Context, Result},
+    clap::Parser,
+};
 
 #[macro_export]
 macro_rules! label {
@@ -18,6 +21,8 @@ macro_rules! struct_label {
 
 pub mod utils;
 
+pub mod cli;
+
 pub mod game;
 pub mod run;
 mod logging {
@@ -60,7 +65,8 @@ mod logging {
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
+    let args = cli::Args::parse();
     logging::setup_tracing();
 
-    run::run().await.context("running")
+    run::run(args).await.context("running")
 }
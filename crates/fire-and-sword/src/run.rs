@@ -1,18 +1,25 @@
 use {
     self::window::WindowHandle,
-    crate::game::GameState,
+    crate::{cli::Args, game::GameState},
     anyhow::{Context, Result},
     futures::{FutureExt, Stream, StreamExt},
     rendering::{
+        assets,
         camera::{Camera, SENSITIVITY},
+        model::loader::{loader_for, LoadProgress, PreparedModels},
         render_pass::WithInstance,
-        scene::Scene,
+        shader::SHADERS_SPV_PATH,
     },
     shader_types::{light_source::LightSource, Color, Vec2, Vec3, Vec4},
-    std::{collections::BTreeMap, future::ready},
+    std::{
+        collections::BTreeMap,
+        future::{ready, Future},
+        path::PathBuf,
+        sync::Arc,
+    },
     tap::prelude::*,
     tokio::time::Instant,
-    tracing::{instrument, warn},
+    tracing::{info, instrument, warn},
     window::WindowingEvent,
     winit::{
         dpi::PhysicalSize,
@@ -25,6 +32,8 @@ use {
 mod config;
 
 pub mod rendering;
+pub mod ui;
+pub mod watch;
 pub mod window;
 
 fn direction_from_look_and_speed(look: Vec3, speed: Vec3) -> Vec3 {
@@ -56,21 +65,38 @@ pub fn game_clock_v2() -> impl Stream<Item = ()> {
 }
 
 pub enum AppEvent {
-    Key(KeyCode, ElementState),
+    /// with the text the key produced, if any
+    Key(KeyCode, ElementState, Option<String>),
     MouseMoved(Vec2),
+    /// cursor position, buttons and scrolling, only used by the ui
+    Pointer(WindowEvent),
     Tick,
     Redraw,
     Resize(PhysicalSize<u32>),
+    FileChanged(PathBuf),
+    /// the map, once a worker is done preparing it
+    Loaded(Result<Box<dyn PreparedModels>>),
+    /// the map again, after it changed on disk
+    Reloaded(Result<Box<dyn PreparedModels>>),
     Exit,
 }
 
+/// reading and decoding happen on a blocking worker, the gpu upload is left to whoever gets the result
+fn prepare_map(map: PathBuf, progress: Arc<LoadProgress>) -> impl Future<Output = Result<Box<dyn PreparedModels>>> {
+    tokio::task::spawn_blocking(move || loader_for(&map).and_then(|loader| loader.prepare(&map, &progress))).map(|joined| {
+        joined
+            .context("preparing the map panicked")
+            .and_then(std::convert::identity)
+    })
+}
+
 #[derive(Default)]
 struct KeyboardState(BTreeMap<KeyCode, ElementState>);
 
 const LIGHT_POSITON: Vec4 = Vec4::new(20., 5., 20., 1.);
 
 #[instrument]
-pub async fn run() -> Result<()> {
+pub async fn run(args: Args) -> Result<()> {
     let WindowHandle {
         window,
         events,
@@ -83,26 +109,33 @@ pub async fn run() -> Result<()> {
             color: Color([1., 1., 1., 1.]),
         }],
         scene: Default::default(),
+        loading: None,
         camera: Camera::new(
-            Default::default(),
+            args.camera_position,
             window
                 .surface_size()
                 .pipe_ref(|s| (s.width as _, s.height as _)),
-        ),
+        )
+        .tap_mut(|camera| {
+            camera.rotation_mut(|yaw, pitch| {
+                *yaw = args.camera_yaw.to_radians();
+                *pitch = args.camera_pitch.to_radians();
+            })
+        }),
     };
 
     let mut state = rendering::State::new(&*window, &game_state)
         .await
         .context("creating renderer state")?;
 
-    let scene = gltf::import_slice(include_bytes!("../../../assets/AntiqueCamera.glb"))
-        .context("loading gltf map")
-        .and_then(|gltf| Scene::load_all(&gltf).context("loading all models from gltf"))
-        .map(|map| map.head)
-        .context("loading blender scene")?;
-    game_state.scene = Some(scene);
+    // the map and whatever it pulled in the last time it was loaded
+    let (watched, watched_receiver) = tokio::sync::watch::channel(vec![args.map.clone()]);
+    let progress = Arc::new(LoadProgress::default());
+    let loading = prepare_map(args.map.clone(), progress.clone());
+    game_state.loading = Some(progress);
 
     let mut keyboard_state = KeyboardState::default();
+    let mut ui = ui::Ui::default();
 
     let mut events = events
         .filter_map(|event| match event {
@@ -116,10 +149,17 @@ pub async fn run() -> Result<()> {
                         KeyEvent {
                             physical_key: PhysicalKey::Code(key),
                             state,
+                            text,
                             ..
                         },
                     ..
-                } => AppEvent::Key(key, state).pipe(Some).pipe(ready),
+                } => AppEvent::Key(key, state, text.as_ref().map(ToString::to_string))
+                    .pipe(Some)
+                    .pipe(ready),
+                pointer @ (WindowEvent::PointerMoved { .. }
+                | WindowEvent::PointerLeft { .. }
+                | WindowEvent::PointerButton { .. }
+                | WindowEvent::MouseWheel { .. }) => AppEvent::Pointer(pointer).pipe(Some).pipe(ready),
 
                 WindowEvent::CloseRequested => AppEvent::Exit.pipe(Some).pipe(ready),
                 WindowEvent::RedrawRequested => AppEvent::Redraw.pipe(Some).pipe(ready),
@@ -130,13 +170,26 @@ pub async fn run() -> Result<()> {
             _ => None.pipe(ready),
         })
         .pipe(|events| {
-            [events.boxed(), game_clock_v2().map(|_| AppEvent::Tick).boxed()]
-                .pipe(futures::stream::iter)
-                .flatten_unordered(8)
+            [
+                events.boxed(),
+                game_clock_v2().map(|_| AppEvent::Tick).boxed(),
+                watch::watch_file(SHADERS_SPV_PATH.into())
+                    .map(AppEvent::FileChanged)
+                    .boxed(),
+                futures::stream::once(loading).map(AppEvent::Loaded).boxed(),
+                // one reload at a time, changes made meanwhile are picked up once it is done
+                watch::watch_files(watched_receiver)
+                    .then(|_| prepare_map(args.map.clone(), Default::default()))
+                    .map(AppEvent::Reloaded)
+                    .boxed(),
+            ]
+            .pipe(futures::stream::iter)
+            .flatten_unordered(8)
         });
-    state.render_game_state(&game_state).await?;
+    state.render_game_state(&game_state, None).await?;
     while let Some(event) = events.next().await {
         match event {
+            AppEvent::MouseMoved(_) if ui.open => {}
             AppEvent::MouseMoved(by) => {
                 if let Err(reason) = window
                     .set_cursor_grab(winit::window::CursorGrabMode::Confined)
@@ -149,32 +202,132 @@ pub async fn run() -> Result<()> {
                     .camera
                     .update_rotation(by.x * SENSITIVITY, by.y * SENSITIVITY);
             }
-            AppEvent::Key(key, state) => {
-                keyboard_state.0.insert(key, state);
-            }
-            AppEvent::Redraw => state
-                .render_game_state(&game_state)
-                .await
-                .context("rendering failed")
-                .or_else(|reason| match reason {
-                    reason if format!("{reason:?}").contains("timeout") => {
-                        warn!("timeout: {reason:?}");
-                        Ok(())
+            AppEvent::Pointer(event) => ui.push_window_event(&event, window.scale_factor()),
+            AppEvent::Key(key, key_state, text) => {
+                ui.push_key(key, key_state, text);
+                let previous = keyboard_state.0.insert(key, key_state);
+                let fresh_press = key_state.is_pressed() && !previous.is_some_and(|previous| previous.is_pressed());
+                if key == config::UI_KEY && fresh_press {
+                    ui.toggle();
+                    // mouse look grabs it again on the next motion
+                    if ui.open {
+                        if let Err(reason) = window
+                            .set_cursor_grab(winit::window::CursorGrabMode::None)
+                            .context("releasing cursor")
+                            .map(|_| window.set_cursor_visible(true))
+                        {
+                            tracing::warn!("could not release cursor:\n{reason:?}");
+                        }
                     }
-                    other => Err(other),
-                })?,
+                }
+                if key == config::DEBUG_VIEW_KEY && fresh_press {
+                    state
+                        .cycle_debug_view(game_state.light_sources.len())
+                        .await
+                        .context("switching debug view")?;
+                }
+                if key == config::SHADING_KEY && fresh_press {
+                    state.toggle_shading();
+                }
+                if key == config::PROFILER_KEY && fresh_press {
+                    state.toggle_profiling();
+                }
+                if key == config::DUMP_KEY && fresh_press {
+                    if let Err(reason) = state
+                        .dump_gpu_state(game_state.scene.as_ref(), &game_state.light_sources)
+                        .await
+                    {
+                        tracing::warn!("dumping gpu state:\n{reason:?}");
+                    }
+                }
+            }
+            AppEvent::Redraw => {
+                let (frame, changes) = ui
+                    .run(&mut game_state, state.size, window.scale_factor())
+                    .map(|(frame, changes)| (Some(frame), changes))
+                    .unwrap_or_default();
+                if changes.light_sources {
+                    state
+                        .update_light_sources(&game_state.light_sources)
+                        .await
+                        .context("updating light sources")?;
+                }
+                changes
+                    .materials
+                    .iter()
+                    .map(|(material, factors)| material.as_ref().set_factors(*factors))
+                    .pipe(futures::future::try_join_all)
+                    .await
+                    .context("updating materials")?;
+                state
+                    .render_game_state(&game_state, frame)
+                    .await
+                    .context("rendering failed")
+                    .or_else(|reason| match reason {
+                        reason if format!("{reason:?}").contains("timeout") => {
+                            warn!("timeout: {reason:?}");
+                            Ok(())
+                        }
+                        other => Err(other),
+                    })?
+            }
             AppEvent::Resize(physical_size) => {
                 state.resize(physical_size);
                 game_state
                     .camera
                     .resize(physical_size.pipe(|PhysicalSize { width, height }| (width as _, height as _)));
             }
+            AppEvent::FileChanged(path) => {
+                if let Err(reason) = state.reload_shaders(&path).await {
+                    warn!("keeping previous shaders:\n{reason:?}");
+                }
+            }
+            AppEvent::Loaded(prepared) => {
+                // the gpu upload happens here, on the render thread
+                let scene = prepared
+                    .tap_ok(|prepared| {
+                        watched.send_replace(prepared.sources());
+                    })
+                    .and_then(|prepared| prepared.load_scene(&args.scene))
+                    .with_context(|| format!("loading scene {:?} from [{}]", args.scene, args.map.display()))?;
+                game_state.scene = Some(scene);
+                game_state.loading = None;
+            }
+            AppEvent::Reloaded(prepared) => {
+                // otherwise the cache would hand back what was loaded from the old files
+                watched
+                    .borrow()
+                    .iter()
+                    .for_each(|source| assets::forget(source));
+                match prepared
+                    .tap_ok(|prepared| {
+                        watched.send_replace(prepared.sources());
+                    })
+                    .and_then(|prepared| prepared.load_scene(&args.scene))
+                {
+                    Ok(scene) => {
+                        info!("reloaded [{}]", args.map.display());
+                        let playback = game_state
+                            .scene
+                            .as_ref()
+                            .and_then(|scene| scene.animations.as_ref())
+                            .map(|animations| animations.playback);
+                        game_state.scene = Some(scene.tap_mut(|scene| {
+                            if let Some((animations, playback)) = scene.animations.as_mut().zip(playback) {
+                                animations.keep_playback(playback);
+                            }
+                        }));
+                    }
+                    Err(reason) => warn!("keeping previous map:\n{reason:?}"),
+                }
+            }
             AppEvent::Exit => std::process::exit(0),
             AppEvent::Tick => {
                 // inputs
                 keyboard_state
                     .0
                     .iter()
+                    .filter(|_| !ui.wants_keyboard())
                     .filter_map(|(k, v)| v.is_pressed().then_some(k))
                     .filter_map(|key| match key {
                         KeyCode::KeyW | KeyCode::ArrowUp => Some(Vec3::Z),
@@ -192,6 +345,11 @@ pub async fn run() -> Result<()> {
                         })
                     });
 
+                // animation
+                if let Some(scene) = game_state.scene.as_mut() {
+                    scene.animate(config::TICK_INTERVAL);
+                }
+
                 // render
                 state.window.request_redraw();
             }
@@ -1,2 +1,13 @@
 pub const FRAMES_PER_SECOND: usize = 30;
 pub const TICK_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_micros(1_000_000 / (FRAMES_PER_SECOND as u64));
+pub const FILE_WATCH_INTERVAL: tokio::time::Duration = tokio::time::Duration::from_millis(500);
+/// cycles through [crate::run::rendering::debug_view::DebugView]s
+pub const DEBUG_VIEW_KEY: winit::keyboard::KeyCode = winit::keyboard::KeyCode::F3;
+/// switches between forward and deferred shading
+pub const SHADING_KEY: winit::keyboard::KeyCode = winit::keyboard::KeyCode::F4;
+/// turns timing every pass on the gpu on and off
+pub const PROFILER_KEY: winit::keyboard::KeyCode = winit::keyboard::KeyCode::F6;
+/// logs what the gpu holds for the light sources, their clusters and the meshes of the scene
+pub const DUMP_KEY: winit::keyboard::KeyCode = winit::keyboard::KeyCode::F5;
+/// opens and closes the inspector panels
+pub const UI_KEY: winit::keyboard::KeyCode = winit::keyboard::KeyCode::F1;
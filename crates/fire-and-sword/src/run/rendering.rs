@@ -5,19 +5,27 @@ use {
     },
     anyhow::{Context, Result},
     camera::CameraPlugin,
+    debug_view::DebugView,
+    deferred::{GBuffer, Shading, LIGHT_VOLUME_MATERIAL},
     futures::TryFutureExt,
-    instance::InstancePlugin,
+    hud::FrameCounter,
     itertools::Itertools,
     light_source::LightSourcePlugin,
-    model::{material::MaterialPlugin, mesh::MeshPlugin},
+    particles::ParticleSystem,
+    pipeline::{PassFeatures, PipelineCache, PipelineFeatures},
     render_pass::WithInstance,
-    std::{future::ready, iter::once, ops::Range},
+    scene::Scene,
+    shader::ShaderPath,
+    shader_types::{bytemuck, light_source::LightSource, Vec2},
+    std::{future::ready, iter::once, ops::Range, path::Path},
     tap::prelude::*,
-    tracing::{instrument, trace},
+    text::GlyphAtlas,
+    tracing::{info, instrument, trace, warn},
+    ui::{UiFrame, UiRenderer},
     wgpu::{Color, CommandEncoder},
     wgpu_ext::{
-        bind_group::HasBindGroup,
         global_context::{init_queue, queue},
+        profiler::GpuProfiler,
     },
     winit::{dpi::PhysicalSize, window::Window},
 };
@@ -26,12 +34,24 @@ pub mod wgpu_ext;
 
 pub mod identify;
 
+pub mod animation;
+pub mod assets;
 pub mod camera;
+pub mod debug_view;
+pub mod deferred;
+pub mod hud;
 pub mod instance;
 pub mod light_source;
 pub mod model;
+pub mod particles;
+pub mod pipeline;
+pub mod reflection;
 pub mod scene;
+pub mod shader;
+pub mod skin;
+pub mod text;
 pub mod texture;
+pub mod ui;
 
 pub mod render_pass;
 
@@ -45,20 +65,70 @@ impl<T> Range<T> {
     }
 }
 
+/// enabled whenever the adapter has them
+const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::TIMESTAMP_QUERY)
+    .union(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS);
+const LIGHT_GIZMO_RADIUS: f32 = 0.25;
+const CLEAR_COLOR: Color = Color {
+    r: 0.1,
+    g: 0.1,
+    b: 0.1,
+    a: 1.0,
+};
+
+fn surface_attachment(view: &wgpu::TextureView, load: wgpu::LoadOp<Color>) -> Option<wgpu::RenderPassColorAttachment<'_>> {
+    Some(wgpu::RenderPassColorAttachment {
+        view,
+        resolve_target: None,
+        ops: wgpu::Operations {
+            load,
+            store: wgpu::StoreOp::Store,
+        },
+    })
+}
+
+fn depth_attachment(view: &wgpu::TextureView, load: wgpu::LoadOp<f32>) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+    wgpu::RenderPassDepthStencilAttachment {
+        view,
+        depth_ops: Some(wgpu::Operations {
+            load,
+            store: wgpu::StoreOp::Store,
+        }),
+        stencil_ops: None,
+    }
+}
+
 pub struct State<'a> {
     pub surface: wgpu::Surface<'a>,
     pub config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
     pub window: &'a dyn Window,
-    pub render_pipeline: wgpu::RenderPipeline,
+    pub pipeline_cache: PipelineCache,
+    pub pass_features: PassFeatures,
+    pub shader_path: ShaderPath,
+    pub debug_view: DebugView,
+    pub glyph_atlas: GlyphAtlas,
+    pub frame_counter: FrameCounter,
+    pub ui_renderer: UiRenderer,
+    /// [None] when the adapter can't time passes
+    pub gpu_profiler: Option<GpuProfiler>,
     pub camera_plugin: CameraPlugin,
     pub light_source_plugin: LightSourcePlugin,
     pub depth_texture: texture::Texture,
+    pub shading: Shading,
+    /// only drawn into with [Shading::Deferred]
+    pub g_buffer: GBuffer,
     pub pass_buffer: self::render_pass::PassBuffer,
 }
 
 impl<'a> State<'a> {
-    pub async fn new(window: &'a dyn Window, GameState { camera, scene, light_sources }: &GameState) -> Result<Self> {
+    pub async fn new(
+        window: &'a dyn Window,
+        GameState {
+            camera, scene, light_sources, ..
+        }: &GameState,
+    ) -> Result<Self> {
         let size = window.surface_size();
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
@@ -104,12 +174,13 @@ impl<'a> State<'a> {
             desired_maximum_frame_latency: 2,
         };
 
+        let shader_path = ShaderPath::for_adapter(&adapter);
         let (device_handle, queue_handle) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: Some("main device"),
-                    required_features: wgpu::Features::SPIRV_SHADER_PASSTHROUGH | wgpu::Features::MAPPABLE_PRIMARY_BUFFERS,
-                    required_limits: wgpu::Limits::default().tap_mut(|limits| limits.max_bind_groups = 5),
+                    required_features: shader_path.required_features() | (adapter.features() & OPTIONAL_FEATURES),
+                    required_limits: wgpu::Limits::default().tap_mut(|limits| limits.max_bind_groups = 6),
                     memory_hints: Default::default(),
                 },
                 None,
@@ -121,96 +192,150 @@ impl<'a> State<'a> {
         init_device(device_handle);
         init_queue(queue_handle);
         let depth_texture = texture::Texture::depth_texture((config.width, config.height), "depth texture");
+        let g_buffer = GBuffer::new((config.width, config.height), &depth_texture);
         // building the pipeline
-        let shader = unsafe { device().create_shader_module_spirv(&wgpu::include_spirv_raw!("../../../../shaders.spv")) };
+        reflection::validate_spirv(shader::SHADERS_SPV, pipeline::entry_point_sets).context("validating shaders.spv")?;
+        let shader = shader_path
+            .create_module("shaders.spv", shader::SHADERS_SPV)
+            .await
+            .context("loading shaders")?;
 
+        let glyph_atlas = GlyphAtlas::new().context("building glyph atlas")?;
         let camera_plugin = CameraPlugin::new(camera);
         // let instance_plugin = InstancePlugin::new(instances);
         let light_source_plugin = LightSourcePlugin::new(light_sources);
-
-        let render_pipeline_layout = device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[
-                // 0
-                CameraPlugin::bind_group_layout(),
-                // 1
-                MeshPlugin::bind_group_layout(),
-                // 2
-                MaterialPlugin::bind_group_layout(),
-                // 3
-                InstancePlugin::bind_group_layout(),
-                // 4
-                LightSourcePlugin::bind_group_layout(),
-            ],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline = device().create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("main_vs"),
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("main_fs"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                // WARN: huge perf hit
-                // cull_mode: None,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+        let pipeline_cache = PipelineCache::new(shader);
+        let pass_features = PassFeatures::new(config.format);
+        let ui_renderer = UiRenderer::new(config.format);
 
         Ok(Self {
             surface,
             config,
             size,
             window,
-            render_pipeline,
+            pipeline_cache,
+            pass_features,
+            shader_path,
+            debug_view: Default::default(),
+            glyph_atlas,
+            frame_counter: Default::default(),
+            ui_renderer,
+            gpu_profiler: None,
             camera_plugin,
             light_source_plugin,
             depth_texture,
+            shading: Default::default(),
+            g_buffer,
             pass_buffer: Default::default(),
         })
     }
 
+    /// keeps the current shaders and pipelines if the new module doesn't compile
+    #[instrument(skip(self))]
+    pub async fn reload_shaders(&mut self, path: &Path) -> Result<()> {
+        tokio::fs::read(path)
+            .await
+            .with_context(|| format!("reading [{}]", path.display()))
+            .and_then(|spirv| reflection::validate_spirv(&spirv, pipeline::entry_point_sets).map(|_| spirv))
+            .pipe(ready)
+            .and_then(|spirv| {
+                let shader_path = self.shader_path;
+                async move { shader_path.create_module("shaders.spv", &spirv).await }
+            })
+            .and_then(|shader| self.pipeline_cache.reload(shader))
+            .await
+            .context("reloading shaders")
+    }
+
+    /// the number of light sources is fixed when the renderer is created
+    pub async fn update_light_sources(&mut self, light_sources: &[LightSource]) -> Result<()> {
+        let light_sources = light_sources.to_vec();
+        self.light_source_plugin
+            .buffer
+            .write(0..light_sources.len() as _, move |buf| buf.copy_from_slice(&light_sources))
+            .await
+            .context("writing light sources")
+    }
+
+    /// logs what the gpu holds for the light sources, their clusters and the meshes of `scene`, reading it back waits for every frame in flight
+    pub async fn dump_gpu_state(&self, scene: Option<&Scene>, light_sources: &[LightSource]) -> Result<()> {
+        let stale = self
+            .light_source_plugin
+            .buffer
+            .read(0..light_sources.len() as u64)
+            .await
+            .context("reading light sources")?
+            .iter()
+            .zip(light_sources)
+            .filter(|(on_gpu, edited)| bytemuck::bytes_of(*on_gpu) != bytemuck::bytes_of(*edited))
+            .count();
+        info!("[{stale}] of [{}] light sources on the gpu differ from the edited ones", light_sources.len());
+        self.light_source_plugin.dump_clusters().await?;
+        let meshes = scene
+            .iter()
+            .flat_map(|scene| scene.nodes.iter().flat_map(|node| node.primitives()))
+            .map(|primitive| &primitive.mesh)
+            .unique()
+            .collect_vec();
+        for mesh in &meshes {
+            mesh.as_ref()
+                .check_indices()
+                .await
+                .with_context(|| format!("mesh {mesh:?}"))?;
+        }
+        info!("indices of [{}] meshes stay within their vertices", meshes.len());
+        Ok(())
+    }
+
+    /// skips views the device can't draw
+    pub async fn cycle_debug_view(&mut self, light_count: usize) -> Result<()> {
+        let mut debug_view = self.debug_view.next(light_count);
+        if debug_view == DebugView::Wireframe
+            && !device()
+                .features()
+                .contains(wgpu::Features::POLYGON_MODE_LINE)
+        {
+            warn!("wireframe needs {:?}, skipping", wgpu::Features::POLYGON_MODE_LINE);
+            debug_view = debug_view.next(light_count);
+        }
+        let options = debug_view.options();
+        self.camera_plugin
+            .debug_view_buffer
+            .write(0..1u64, move |buf| {
+                buf[0] = options;
+            })
+            .await
+            .context("writing debug view options")
+            .map(|_| {
+                info!("debug view: {debug_view:?}");
+                self.debug_view = debug_view;
+            })
+    }
+
+    /// off to begin with, turning it on does nothing when the device can't write timestamps
+    pub fn toggle_profiling(&mut self) {
+        self.gpu_profiler = match self.gpu_profiler.take() {
+            Some(_) => None,
+            None => GpuProfiler::new(),
+        }
+        .tap(|profiler| info!("gpu profiling: {}", profiler.is_some()));
+    }
+
+    /// debug views other than the shaded one are drawn forward either way
+    pub fn toggle_shading(&mut self) {
+        self.shading = self
+            .shading
+            .toggle()
+            .tap(|shading| info!("shading: {shading:?}"));
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.depth_texture = texture::Texture::depth_texture(self.config.pipe_ref(|c| (c.width, c.height)), "depth texture");
+            self.g_buffer = GBuffer::new((self.config.width, self.config.height), &self.depth_texture);
             self.surface.configure(device(), &self.config);
         }
     }
@@ -245,9 +370,46 @@ impl<'a> State<'a> {
             .await
             .with_context(|| format!("running on encoder: {label}"))
     }
-    pub async fn render_game_state(&mut self, GameState { camera, scene, light_sources }: &GameState) -> Result<()> {
-        self.render_pass(|pass| {
+    /// the ui, if there is one, is drawn in its own pass after the scene
+    pub async fn render_game_state(
+        &mut self,
+        GameState {
+            camera,
+            scene,
+            light_sources,
+            loading,
+        }: &GameState,
+        ui: Option<UiFrame>,
+    ) -> Result<()> {
+        let fps = self.frame_counter.tick();
+        let emitters = scene.iter().flat_map(Scene::emitters).collect_vec();
+        ParticleSystem::update(&emitters, camera)
+            .await
+            .context("updating particles")?;
+        if let Some(scene) = scene {
+            scene.update_skins().await.context("updating skins")?;
+        }
+        self.light_source_plugin
+            .update_clusters(camera, Vec2::new(self.config.width as f32, self.config.height as f32))
+            .await
+            .context("updating light clusters")?;
+        let simulate = |encoder: &mut CommandEncoder, pipelines: &mut PipelineCache, profiler: Option<&mut GpuProfiler>| {
+            ParticleSystem::dispatch(encoder, pipelines, profiler, &emitters)
+        };
+        let timings = self
+            .gpu_profiler
+            .as_ref()
+            .map(|profiler| profiler.timings().to_vec());
+        self.render_pass(ui, simulate, |pass| {
             pass.set_camera(*camera);
+            hud::draw_hud(pass, fps, camera, loading.as_deref(), timings.as_deref());
+            if pass.debug_view.shows_gizmos() {
+                pass.axes(Default::default(), 1.);
+                light_sources.iter().enumerate().for_each(|(idx, light)| {
+                    pass.sphere(light.position.truncate(), LIGHT_GIZMO_RADIUS, light.color);
+                    pass.world_text(light.position.truncate(), hud::HUD_TEXT_SIZE, light.color, &format!("light {idx}"));
+                });
+            }
             scene
                 .as_ref()
                 .iter()
@@ -262,9 +424,35 @@ impl<'a> State<'a> {
         .await
         .context("rendering full game state")
     }
+    /// binds what stays the same for every draw and hands `pass` out for queueing the scene
+    fn scene_pass<'pass, 'encoder>(&'pass mut self, pass: &'pass mut wgpu::RenderPass<'encoder>) -> self::render_pass::RenderPass<'pass, 'encoder> {
+        pass.set_bind_group(0, &self.camera_plugin.bind_group, &[]);
+        // pass.set_bind_group(3, &self.instance_plugin.bind_group, &[]);
+        pass.set_bind_group(4, &self.light_source_plugin.bind_group, &[]);
+        // pass.draw_scene_instanced(&self.scene, 0..instances.len() as u32);
+        self::render_pass::RenderPass {
+            camera_plugin: &mut self.camera_plugin,
+            camera: None,
+            buffer: &mut self.pass_buffer,
+            pipelines: &mut self.pipeline_cache,
+            features: self.pass_features,
+            debug_view: self.debug_view,
+            glyph_atlas: &self.glyph_atlas,
+            g_buffer: &self.g_buffer,
+            screen_size: Vec2::new(self.config.width as f32, self.config.height as f32),
+            pass,
+        }
+    }
+
+    /// `with_compute` records into the same encoder, ahead of the render pass, right after lights are assigned to clusters
+    ///
+    /// with [Shading::Deferred] opaque geometry goes through a g-buffer and a lighting pass first,
+    /// the render pass then only draws what is blended and the overlays
     #[instrument(skip_all)]
-    pub async fn render_pass<F>(
+    pub async fn render_pass<C, F>(
         &mut self,
+        ui: Option<UiFrame>,
+        with_compute: C,
         with_render_pass: F,
         // GameState {
         //     camera,
@@ -273,6 +461,7 @@ impl<'a> State<'a> {
         // }: &GameState,
     ) -> Result<()>
     where
+        C: FnOnce(&mut CommandEncoder, &mut PipelineCache, Option<&mut GpuProfiler>),
         F: FnOnce(&mut self::render_pass::RenderPass<'_, '_>) -> Result<()>,
     {
         trace!("flushing camera");
@@ -306,6 +495,10 @@ impl<'a> State<'a> {
         //     })
         //     .await
         //     .context("updating instances")?;
+        // the previous frame has been submitted by now, so its timestamps can be mapped
+        if let Some(profiler) = &mut self.gpu_profiler {
+            profiler.collect();
+        }
         trace!("writing to surface");
         self.surface
             .get_current_texture()
@@ -317,51 +510,105 @@ impl<'a> State<'a> {
                     .create_view(&wgpu::TextureViewDescriptor::default())
                     .pipe(|texture_view| async move {
                         Self::with_command_encoder_async("rendering_to_texture", async |encoder| {
-                            encoder
+                            self.light_source_plugin
+                                .assign_clusters(encoder, &mut self.pipeline_cache, self.gpu_profiler.as_mut());
+                            with_compute(encoder, &mut self.pipeline_cache, self.gpu_profiler.as_mut());
+                            // whichever scene pass comes first queues the frame, the last one draws whatever is left
+                            let mut with_render_pass = Some(with_render_pass);
+                            let deferred = self.shading == Shading::Deferred && self.debug_view == DebugView::Shaded;
+                            if deferred {
+                                encoder
+                                    .begin_render_pass(&wgpu::RenderPassDescriptor {
+                                        label: Some("g-buffer pass"),
+                                        color_attachments: &once(surface_attachment(&texture_view, wgpu::LoadOp::Clear(CLEAR_COLOR)))
+                                            .chain(self.g_buffer.color_attachments())
+                                            .collect_vec(),
+                                        depth_stencil_attachment: Some(depth_attachment(&self.depth_texture.view, wgpu::LoadOp::Clear(1.))),
+                                        timestamp_writes: self
+                                            .gpu_profiler
+                                            .as_mut()
+                                            .and_then(|profiler| profiler.render_pass_writes("g-buffer pass")),
+                                        occlusion_query_set: None,
+                                    })
+                                    .pipe_ref_mut(|pass| self.scene_pass(pass))
+                                    .pipe(|mut pass| {
+                                        with_render_pass
+                                            .take()
+                                            .map_or(Ok(()), |queue| queue(&mut pass))
+                                            .map(|_| pass)
+                                    })
+                                    .pipe(ready)
+                                    .and_then(|pass| pass.finish_g_buffer())
+                                    .await
+                                    .context("finishing up g-buffer pass")?;
+                                encoder
+                                    .begin_render_pass(&wgpu::RenderPassDescriptor {
+                                        label: Some("lighting pass"),
+                                        color_attachments: &[surface_attachment(&texture_view, wgpu::LoadOp::Load)],
+                                        depth_stencil_attachment: None,
+                                        timestamp_writes: self
+                                            .gpu_profiler
+                                            .as_mut()
+                                            .and_then(|profiler| profiler.render_pass_writes("lighting pass")),
+                                        occlusion_query_set: None,
+                                    })
+                                    .pipe_ref_mut(|pass| {
+                                        self.g_buffer.draw_light_volumes(
+                                            pass,
+                                            self.pipeline_cache.get_or_create(PipelineFeatures {
+                                                material: LIGHT_VOLUME_MATERIAL,
+                                                pass: PassFeatures {
+                                                    depth_format: None,
+                                                    ..self.pass_features
+                                                },
+                                            }),
+                                            &self.camera_plugin,
+                                            &self.light_source_plugin,
+                                        )
+                                    });
+                            }
+                            let (surface_load, depth_load) = match deferred {
+                                true => (wgpu::LoadOp::Load, wgpu::LoadOp::Load),
+                                false => (wgpu::LoadOp::Clear(CLEAR_COLOR), wgpu::LoadOp::Clear(1.)),
+                            };
+                            let finished = encoder
                                 .begin_render_pass(&wgpu::RenderPassDescriptor {
                                     label: Some("render pass"),
-                                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                                        view: &texture_view,
-                                        resolve_target: None,
-                                        ops: wgpu::Operations {
-                                            store: wgpu::StoreOp::Store,
-                                            load: wgpu::LoadOp::Clear(Color {
-                                                r: 0.1,
-                                                g: 0.1,
-                                                b: 0.1,
-                                                a: 1.0,
-                                            }),
-                                        },
-                                    })],
-                                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                                        view: &self.depth_texture.view,
-                                        depth_ops: Some(wgpu::Operations {
-                                            load: wgpu::LoadOp::Clear(1.),
-                                            store: wgpu::StoreOp::Store,
-                                        }),
-                                        stencil_ops: None,
-                                    }),
-                                    timestamp_writes: None,
+                                    color_attachments: &[surface_attachment(&texture_view, surface_load)],
+                                    depth_stencil_attachment: Some(depth_attachment(&self.depth_texture.view, depth_load)),
+                                    timestamp_writes: self
+                                        .gpu_profiler
+                                        .as_mut()
+                                        .and_then(|profiler| profiler.render_pass_writes("render pass")),
                                     occlusion_query_set: None,
                                 })
-                                .tap_mut(|pass| {
-                                    pass.set_pipeline(&self.render_pipeline);
-                                    pass.set_bind_group(0, &self.camera_plugin.bind_group, &[]);
-                                    // pass.set_bind_group(3, &self.instance_plugin.bind_group, &[]);
-                                    pass.set_bind_group(4, &self.light_source_plugin.bind_group, &[]);
-                                    // pass.draw_scene_instanced(&self.scene, 0..instances.len() as u32);
-                                })
-                                .pipe_ref_mut(|pass| self::render_pass::RenderPass {
-                                    camera_plugin: &mut self.camera_plugin,
-                                    camera: None,
-                                    buffer: &mut self.pass_buffer,
-                                    pass,
+                                .pipe_ref_mut(|pass| self.scene_pass(pass))
+                                .pipe(|mut pass| {
+                                    with_render_pass
+                                        .take()
+                                        .map_or(Ok(()), |queue| queue(&mut pass))
+                                        .map(|_| pass)
                                 })
-                                .pipe(|mut pass| with_render_pass(&mut pass).map(|_| pass))
                                 .pipe(ready)
                                 .and_then(|pass| pass.finish())
                                 .await
-                                .context("finishing up render pass")
+                                .context("finishing up render pass");
+                            finished.map(|_| {
+                                if let Some(ui) = ui {
+                                    self.ui_renderer.draw(
+                                        encoder,
+                                        &texture_view,
+                                        (self.config.width, self.config.height),
+                                        ui,
+                                        self.gpu_profiler
+                                            .as_mut()
+                                            .and_then(|profiler| profiler.render_pass_writes("ui pass")),
+                                    )
+                                }
+                                if let Some(profiler) = &mut self.gpu_profiler {
+                                    profiler.resolve(encoder);
+                                }
+                            })
                         })
                         .await
                     })
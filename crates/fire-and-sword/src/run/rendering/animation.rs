@@ -0,0 +1,449 @@
+use {
+    super::model::load_gltf::GltfImport,
+    anyhow::{Context, Result},
+    gltf::animation::{util::ReadOutputs, Interpolation},
+    itertools::Itertools,
+    shader_types::{glam::Affine3A, Quat, Vec3},
+    std::{
+        collections::HashMap,
+        ops::{Add, Mul},
+        time::Duration,
+    },
+    tap::prelude::*,
+};
+
+/// keyframe times in seconds, cubic splines keep an in tangent, the value and an out tangent for every one of them
+pub struct Keyframes<T> {
+    times: Vec<f32>,
+    values: Vec<T>,
+    interpolation: Interpolation,
+}
+
+/// what a value needs so it can be interpolated between keyframes
+pub trait Animated: Copy + Add<Output = Self> + Mul<f32, Output = Self> {
+    fn lerp(self, other: Self, t: f32) -> Self;
+    /// for values that drift off their valid range while being blended
+    fn normalized(self) -> Self {
+        self
+    }
+}
+
+impl Animated for Vec3 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Vec3::lerp(self, other, t)
+    }
+}
+
+impl Animated for Quat {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self.slerp(other, t)
+    }
+
+    fn normalized(self) -> Self {
+        self.normalize()
+    }
+}
+
+impl<T: Animated> Keyframes<T> {
+    fn new(times: Vec<f32>, values: Vec<T>, interpolation: Interpolation) -> Result<Self> {
+        let per_keyframe = match interpolation {
+            Interpolation::CubicSpline => 3,
+            Interpolation::Linear | Interpolation::Step => 1,
+        };
+        match !times.is_empty() && values.len() == times.len() * per_keyframe {
+            true => Ok(Self { times, values, interpolation }),
+            false => anyhow::bail!("[{}] keyframes but [{}] values for {interpolation:?} interpolation", times.len(), values.len()),
+        }
+    }
+
+    fn value(&self, keyframe: usize) -> T {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[keyframe * 3 + 1],
+            Interpolation::Linear | Interpolation::Step => self.values[keyframe],
+        }
+    }
+
+    /// holds the first and last value before and after the keyframes
+    pub fn sample(&self, time: f32) -> T {
+        let next = self.times.partition_point(|keyframe| *keyframe <= time);
+        match next {
+            0 => self.value(0),
+            next if next == self.times.len() => self.value(next - 1),
+            next => {
+                let previous = next - 1;
+                let delta = self.times[next] - self.times[previous];
+                let t = (time - self.times[previous]) / delta;
+                match self.interpolation {
+                    Interpolation::Step => self.value(previous),
+                    Interpolation::Linear => self.value(previous).lerp(self.value(next), t),
+                    Interpolation::CubicSpline => {
+                        // hermite spline, tangents are scaled by the time between the keyframes
+                        let (t2, t3) = (t * t, t * t * t);
+                        let out_tangent = self.values[previous * 3 + 2];
+                        let in_tangent = self.values[next * 3];
+                        (self.value(previous) * (2. * t3 - 3. * t2 + 1.)
+                            + out_tangent * (delta * (t3 - 2. * t2 + t))
+                            + self.value(next) * (-2. * t3 + 3. * t2)
+                            + in_tangent * (delta * (t3 - t2)))
+                            .normalized()
+                    }
+                }
+            }
+        }
+    }
+
+    fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or_default()
+    }
+}
+
+pub enum Channel {
+    Translation(Keyframes<Vec3>),
+    Rotation(Keyframes<Quat>),
+    Scale(Keyframes<Vec3>),
+}
+
+/// where a node sits when no clip drives it, kept apart from its transform so poses never build on each other
+#[derive(Debug, Clone, Copy)]
+pub struct RestPose {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for RestPose {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+/// the parts of a node transform a clip overrides at some point in time, the rest is left as the node has it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pose {
+    pub translation: Option<Vec3>,
+    pub rotation: Option<Quat>,
+    pub scale: Option<Vec3>,
+}
+
+impl Pose {
+    pub fn apply(&self, rest: &RestPose) -> Affine3A {
+        Affine3A::from_scale_rotation_translation(
+            self.scale.unwrap_or(rest.scale),
+            self.rotation.unwrap_or(rest.rotation),
+            self.translation.unwrap_or(rest.translation),
+        )
+    }
+}
+
+/// a glTF animation, channels are keyed by the index of the node they drive
+pub struct Clip {
+    pub name: Option<String>,
+    /// in seconds, until the last keyframe of any channel
+    pub duration: f32,
+    channels: Vec<(usize, Channel)>,
+}
+
+impl Clip {
+    /// morph target weights have nothing to drive yet, those channels are skipped with a warning
+    pub fn load(GltfImport { buffers, .. }: &GltfImport, animation: gltf::Animation<'_>) -> Result<Self> {
+        let name = animation.name().map(str::to_owned);
+        animation
+            .channels()
+            .filter_map(|channel| {
+                let node = channel.target().node().index();
+                let interpolation = channel.sampler().interpolation();
+                let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(|data| data.as_ref()));
+                let times = match reader.read_inputs() {
+                    Some(times) => times.collect_vec(),
+                    None => return Some(Err(anyhow::anyhow!("channel for node #{node} has no keyframe times"))),
+                };
+                match reader.read_outputs()? {
+                    ReadOutputs::Translations(values) => Keyframes::new(times, values.map(Vec3::from).collect(), interpolation).map(Channel::Translation),
+                    ReadOutputs::Rotations(values) => {
+                        Keyframes::new(times, values.into_f32().map(Quat::from_array).collect(), interpolation).map(Channel::Rotation)
+                    }
+                    ReadOutputs::Scales(values) => Keyframes::new(times, values.map(Vec3::from).collect(), interpolation).map(Channel::Scale),
+                    ReadOutputs::MorphTargetWeights(_) => {
+                        tracing::warn!(
+                            "animation [{}] drives morph target weights of node #{node}, skipping them",
+                            name.as_deref().unwrap_or("(unnamed)")
+                        );
+                        return None;
+                    }
+                }
+                .with_context(|| format!("reading {:?} channel for node #{node}", channel.target().property()))
+                .map(|channel| (node, channel))
+                .pipe(Some)
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(|channels| Self {
+                duration: channels
+                    .iter()
+                    .map(|(_, channel)| match channel {
+                        Channel::Translation(keyframes) | Channel::Scale(keyframes) => keyframes.duration(),
+                        Channel::Rotation(keyframes) => keyframes.duration(),
+                    })
+                    .fold(0., f32::max),
+                name,
+                channels,
+            })
+    }
+
+    /// every node the clip drives, posed at `time` seconds in
+    pub fn pose(&self, time: f32) -> HashMap<usize, Pose> {
+        self.channels
+            .iter()
+            .fold(HashMap::new(), |poses, (node, channel)| {
+                poses.tap_mut(|poses| {
+                    let pose = poses.entry(*node).or_insert_with(Pose::default);
+                    match channel {
+                        Channel::Translation(keyframes) => pose.translation = Some(keyframes.sample(time)),
+                        Channel::Rotation(keyframes) => pose.rotation = Some(keyframes.sample(time)),
+                        Channel::Scale(keyframes) => pose.scale = Some(keyframes.sample(time)),
+                    }
+                })
+            })
+    }
+}
+
+/// which clip is playing and how far along it is, advanced on every game tick
+#[derive(Debug, Clone, Copy)]
+pub struct Playback {
+    pub clip: usize,
+    /// in seconds
+    pub time: f32,
+    pub speed: f32,
+    pub playing: bool,
+    pub looping: bool,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            clip: 0,
+            time: 0.,
+            speed: 1.,
+            playing: true,
+            looping: true,
+        }
+    }
+}
+
+impl Playback {
+    /// clips that don't loop stop at either end, depending on which way they are played
+    pub fn advance(&mut self, by: Duration, duration: f32) {
+        if !self.playing {
+            return;
+        }
+        let time = self.time + by.as_secs_f32() * self.speed;
+        match self.looping && duration > 0. {
+            true => self.time = time.rem_euclid(duration),
+            false => {
+                self.time = time.clamp(0., duration);
+                self.playing = match self.speed > 0. {
+                    true => time < duration,
+                    false => self.speed == 0. || time > 0.,
+                };
+            }
+        }
+    }
+
+    pub fn seek(&mut self, time: f32, duration: f32) {
+        self.time = time.clamp(0., duration);
+    }
+
+    /// starts over if the clip has played to its end
+    pub fn play(&mut self, duration: f32) {
+        if !self.looping && self.speed > 0. && self.time >= duration {
+            self.time = 0.;
+        }
+        self.playing = true;
+    }
+}
+
+/// every clip of a scene and the one currently playing
+pub struct Animations {
+    pub clips: Vec<Clip>,
+    pub playback: Playback,
+}
+
+impl Animations {
+    pub fn load(context: &GltfImport) -> Result<Option<Self>> {
+        context
+            .document
+            .animations()
+            .map(|animation| {
+                let index = animation.index();
+                Clip::load(context, animation).with_context(|| format!("loading animation #{index}"))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(|clips| {
+                (!clips.is_empty()).then(|| Self {
+                    clips,
+                    playback: Default::default(),
+                })
+            })
+    }
+
+    /// carries playback over from before a reload, as long as the clip it was on is still there
+    pub fn keep_playback(&mut self, playback: Playback) {
+        if let Some(clip) = self.clips.get(playback.clip) {
+            self.playback = Playback {
+                time: playback.time.min(clip.duration),
+                ..playback
+            };
+        }
+    }
+
+    pub fn current(&self) -> Option<&Clip> {
+        self.clips.get(self.playback.clip)
+    }
+
+    pub fn advance(&mut self, by: Duration) {
+        if let Some(duration) = self.current().map(|clip| clip.duration) {
+            self.playback.advance(by, duration);
+        }
+    }
+
+    pub fn pose(&self) -> HashMap<usize, Pose> {
+        self.current()
+            .map(|clip| clip.pose(self.playback.time))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframes(values: Vec<Vec3>, interpolation: Interpolation) -> Keyframes<Vec3> {
+        Keyframes::new(vec![0., 1., 3.], values, interpolation).expect("valid keyframes")
+    }
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(actual.abs_diff_eq(expected, 1e-5), "expected {expected}, got {actual}");
+    }
+
+    #[test]
+    fn values_have_to_match_the_keyframes() {
+        assert!(Keyframes::new(vec![0., 1.], vec![Vec3::ZERO], Interpolation::Linear).is_err());
+        assert!(Keyframes::new(vec![0., 1.], vec![Vec3::ZERO; 2], Interpolation::CubicSpline).is_err());
+        assert!(Keyframes::<Vec3>::new(vec![], vec![], Interpolation::Step).is_err());
+    }
+
+    #[test]
+    fn step_holds_the_previous_keyframe() {
+        let keyframes = keyframes(vec![Vec3::X, Vec3::Y, Vec3::Z], Interpolation::Step);
+        assert_near(keyframes.sample(0.), Vec3::X);
+        assert_near(keyframes.sample(0.99), Vec3::X);
+        assert_near(keyframes.sample(1.), Vec3::Y);
+        assert_near(keyframes.sample(2.), Vec3::Y);
+        assert_near(keyframes.sample(-1.), Vec3::X);
+        assert_near(keyframes.sample(10.), Vec3::Z);
+    }
+
+    #[test]
+    fn linear_blends_between_keyframes() {
+        let keyframes = keyframes(vec![Vec3::ZERO, Vec3::X, Vec3::new(3., 0., 0.)], Interpolation::Linear);
+        assert_near(keyframes.sample(0.5), Vec3::new(0.5, 0., 0.));
+        assert_near(keyframes.sample(1.), Vec3::X);
+        // the second gap is twice as long
+        assert_near(keyframes.sample(2.), Vec3::new(2., 0., 0.));
+        assert_near(keyframes.sample(-1.), Vec3::ZERO);
+        assert_near(keyframes.sample(4.), Vec3::new(3., 0., 0.));
+    }
+
+    #[test]
+    fn linear_rotations_stay_normalized() {
+        let keyframes = Keyframes::new(
+            vec![0., 1.],
+            vec![Quat::IDENTITY, Quat::from_rotation_y(std::f32::consts::FRAC_PI_2)],
+            Interpolation::Linear,
+        )
+        .expect("valid keyframes");
+        let halfway = keyframes.sample(0.5);
+        assert!(halfway.is_normalized());
+        assert!(halfway.abs_diff_eq(Quat::from_rotation_y(std::f32::consts::FRAC_PI_4), 1e-5));
+    }
+
+    #[test]
+    fn cubic_spline_follows_the_tangents() {
+        // in tangent, value and out tangent for every keyframe
+        let keyframes = keyframes(
+            vec![
+                Vec3::ZERO,
+                Vec3::ZERO,
+                Vec3::X,
+                Vec3::ZERO,
+                Vec3::ZERO,
+                Vec3::ZERO,
+                Vec3::ZERO,
+                Vec3::Y,
+                Vec3::ZERO,
+            ],
+            Interpolation::CubicSpline,
+        );
+        assert_near(keyframes.sample(0.), Vec3::ZERO);
+        assert_near(keyframes.sample(1.), Vec3::ZERO);
+        // halfway through the first gap the out tangent of the first keyframe adds delta * (t³ - 2t² + t)
+        assert_near(keyframes.sample(0.5), Vec3::new(0.125, 0., 0.));
+        // flat tangents leave a smoothstep between the values
+        assert_near(keyframes.sample(2.), Vec3::new(0., 0.5, 0.));
+        assert_near(keyframes.sample(-1.), Vec3::ZERO);
+        assert_near(keyframes.sample(4.), Vec3::Y);
+    }
+
+    fn playback(time: f32, speed: f32, looping: bool) -> Playback {
+        Playback {
+            time,
+            speed,
+            looping,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn looping_wraps_around_both_ends() {
+        let mut forward = playback(0.5, 1., true);
+        forward.advance(Duration::from_secs_f32(0.75), 1.);
+        assert!((forward.time - 0.25).abs() < 1e-5);
+        assert!(forward.playing);
+
+        let mut backward = playback(0.25, -1., true);
+        backward.advance(Duration::from_secs_f32(0.5), 1.);
+        assert!((backward.time - 0.75).abs() < 1e-5);
+        assert!(backward.playing);
+    }
+
+    #[test]
+    fn clamping_stops_at_the_end_it_runs_into() {
+        let mut forward = playback(0.5, 1., false);
+        forward.advance(Duration::from_secs_f32(0.25), 1.);
+        assert!((forward.time - 0.75).abs() < 1e-5);
+        assert!(forward.playing);
+        forward.advance(Duration::from_secs(1), 1.);
+        assert_eq!(forward.time, 1.);
+        assert!(!forward.playing);
+
+        let mut backward = playback(0.5, -2., false);
+        backward.advance(Duration::from_secs(1), 1.);
+        assert_eq!(backward.time, 0.);
+        assert!(!backward.playing);
+        backward.play(1.);
+        assert_eq!(backward.time, 0., "only clips played forwards start over");
+    }
+
+    #[test]
+    fn paused_playback_stays_put() {
+        let mut paused = Playback {
+            playing: false,
+            ..playback(0.5, 1., true)
+        };
+        paused.advance(Duration::from_secs(1), 1.);
+        assert_eq!(paused.time, 0.5);
+    }
+}
@@ -0,0 +1,116 @@
+use {
+    super::{
+        identify::{WeakWithId, WithId},
+        model::{material::LoadedMaterial, mesh::LoadedMesh},
+        texture::Texture,
+    },
+    anyhow::Result,
+    image::{DynamicImage, Rgba, RgbaImage},
+    std::{
+        cell::RefCell,
+        collections::HashMap,
+        path::{Path, PathBuf},
+    },
+    tap::prelude::*,
+};
+
+thread_local! {
+    /// handles are [std::rc::Rc]s, so every thread would need its own anyway
+    static ASSETS: RefCell<AssetServer> = RefCell::default();
+}
+
+/// where an asset came from, loading the same key twice hands back the same handle for as long as one is alive
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AssetKey {
+    pub source: PathBuf,
+    pub index: AssetIndex,
+}
+
+/// position of an asset within its source, glTF assets go by their index in the document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AssetIndex {
+    /// a single level of detail of a primitive
+    Mesh { mesh: usize, primitive: usize, level: usize },
+    /// [None] for the default material
+    Material(Option<usize>),
+    /// decoded once no matter how many textures sample it
+    Image(usize),
+    /// sources that hold nothing else, like an image file
+    Whole,
+    /// the single white pixel, [AssetKey::source] is empty
+    White,
+}
+
+impl AssetKey {
+    pub fn new(source: &Path, index: AssetIndex) -> Self {
+        Self {
+            source: source.to_owned(),
+            index,
+        }
+    }
+}
+
+#[derive(Default)]
+struct AssetServer {
+    meshes: AssetCache<LoadedMesh>,
+    materials: AssetCache<LoadedMaterial>,
+    textures: AssetCache<Texture>,
+}
+
+/// only weak handles, gpu resources go away with the last [WithId] pointing at them
+struct AssetCache<T>(HashMap<AssetKey, WeakWithId<T>>);
+
+impl<T> Default for AssetCache<T> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+/// `load` runs without the server borrowed, so it can pull in assets of its own
+fn get_or_load<T>(cache: fn(&mut AssetServer) -> &mut AssetCache<T>, key: AssetKey, load: impl FnOnce() -> Result<T>) -> Result<WithId<T>> {
+    match ASSETS.with_borrow_mut(|assets| cache(assets).0.get(&key).and_then(WeakWithId::upgrade)) {
+        Some(handle) => Ok(handle),
+        #[allow(deprecated)]
+        None => load().map(WithId::register).tap_ok(|handle| {
+            ASSETS.with_borrow_mut(|assets| {
+                let cache = &mut cache(assets).0;
+                cache.retain(|_, handle| handle.is_alive());
+                cache.insert(key, handle.downgrade());
+            })
+        }),
+    }
+}
+
+pub fn mesh(key: AssetKey, load: impl FnOnce() -> Result<LoadedMesh>) -> Result<WithId<LoadedMesh>> {
+    get_or_load(|assets| &mut assets.meshes, key, load)
+}
+
+pub fn material(key: AssetKey, load: impl FnOnce() -> Result<LoadedMaterial>) -> Result<WithId<LoadedMaterial>> {
+    get_or_load(|assets| &mut assets.materials, key, load)
+}
+
+pub fn texture(key: AssetKey, load: impl FnOnce() -> Result<Texture>) -> Result<WithId<Texture>> {
+    get_or_load(|assets| &mut assets.textures, key, load)
+}
+
+/// drops every cached handle loaded from `source`, so loading it again reads it anew
+///
+/// handles already out there stay valid, they just won't be handed out anymore
+pub fn forget(source: &Path) {
+    ASSETS.with_borrow_mut(|AssetServer { meshes, materials, textures }| {
+        meshes.0.retain(|key, _| key.source != source);
+        materials.0.retain(|key, _| key.source != source);
+        textures.0.retain(|key, _| key.source != source);
+    })
+}
+
+/// a single white pixel, shared by every material without a texture of its own
+pub fn white() -> WithId<Texture> {
+    texture(AssetKey::new(Path::new(""), AssetIndex::White), || {
+        RgbaImage::from_pixel(1, 1, Rgba([u8::MAX; 4]))
+            .pipe(DynamicImage::ImageRgba8)
+            .pipe(|image| Texture::from_image(&image, Some("WHITE")))
+            .pipe(Ok)
+    })
+    .expect("the white texture always loads")
+}
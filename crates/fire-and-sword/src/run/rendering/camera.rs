@@ -2,7 +2,11 @@ use {
     super::wgpu_ext::{bind_group::HasBindGroup, buffer::uniform::UniformBuffer, global_context::device},
     crate::bind_group_layout,
     glam::{Mat4, Vec3},
-    shader_types::glam,
+    shader_types::{
+        camera::{Z_FAR, Z_NEAR},
+        debug_view::DebugViewOptions,
+        glam,
+    },
     tap::prelude::*,
     wgpu::BindGroup,
 };
@@ -36,28 +40,45 @@ impl Camera {
     }
     // Update rotation based on mouse movement
     pub fn update_rotation(&mut self, delta_x: f32, delta_y: f32) {
-        self.yaw += delta_x * SENSITIVITY;
-        self.pitch -= delta_y * SENSITIVITY;
-        self.pitch = self
-            .pitch
-            .clamp(-std::f32::consts::FRAC_PI_2 + 0.01, std::f32::consts::FRAC_PI_2 - 0.01);
+        self.rotation_mut(|yaw, pitch| {
+            *yaw += delta_x * SENSITIVITY;
+            *pitch -= delta_y * SENSITIVITY;
+        });
+    }
+    /// yaw and pitch in radians, pitch stays short of straight up or down
+    pub fn rotation_mut<R>(&mut self, rotation: impl FnOnce(&mut f32, &mut f32) -> R) -> R {
+        rotation(&mut self.yaw, &mut self.pitch).tap(|_| {
+            self.pitch = self
+                .pitch
+                .clamp(-std::f32::consts::FRAC_PI_2 + 0.01, std::f32::consts::FRAC_PI_2 - 0.01);
+        })
     }
     // Compute the view matrix (right-handed)
-    pub fn get_view_projection(&self) -> Mat4 {
-        let (width, height) = self.size;
+    pub fn view(&self) -> Mat4 {
         let forward = self.look();
         let target = self.position + forward; // Point the camera is looking at
         let up = Vec3::Y; // World up vector (Y-axis)
-        let proj = Mat4::perspective_rh(45., width / height, 0.1, 100.);
-        proj * Mat4::look_at_rh(self.position, target, up)
+        Mat4::look_at_rh(self.position, target, up)
+    }
+    pub fn projection(&self) -> Mat4 {
+        let (width, height) = self.size;
+        Mat4::perspective_rh(45., width / height, Z_NEAR, Z_FAR)
+    }
+    pub fn get_view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+    pub fn position(&self) -> Vec3 {
+        self.position
     }
     pub fn position_mut(&mut self, position: impl FnOnce(&mut Vec3)) {
         position(&mut self.position);
     }
 }
 
+/// everything that stays bound for the whole frame
 pub struct CameraPlugin {
     pub buffer: UniformBuffer<Mat4>,
+    pub debug_view_buffer: UniformBuffer<DebugViewOptions>,
     pub bind_group: BindGroup,
 }
 
@@ -77,6 +98,17 @@ bind_group_layout!(
                 },
                 count: None,
             },
+            // DEBUG VIEW
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
         ],
     }
 );
@@ -86,17 +118,28 @@ impl CameraPlugin {
         camera
             .get_view_projection()
             .pipe(|camera| UniformBuffer::new_init(&camera))
-            .pipe(|buffer| {
+            .pipe(|buffer| (buffer, UniformBuffer::new_init(&DebugViewOptions::default())))
+            .pipe(|(buffer, debug_view_buffer)| {
                 device()
                     .create_bind_group(&wgpu::BindGroupDescriptor {
                         label: struct_label!(),
                         layout: Self::bind_group_layout(),
-                        entries: &[wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: buffer.as_ref().as_entire_binding(),
-                        }],
+                        entries: &[
+                            wgpu::BindGroupEntry {
+                                binding: 0,
+                                resource: buffer.as_ref().as_entire_binding(),
+                            },
+                            wgpu::BindGroupEntry {
+                                binding: 1,
+                                resource: debug_view_buffer.as_ref().as_entire_binding(),
+                            },
+                        ],
+                    })
+                    .pipe(|bind_group| Self {
+                        buffer,
+                        debug_view_buffer,
+                        bind_group,
                     })
-                    .pipe(|bind_group| Self { buffer, bind_group })
             })
     }
 }
@@ -0,0 +1,82 @@
+use {
+    super::pipeline::{EntryPoints, MaterialFeatures, PassFeatures},
+    shader_types::{debug_view::DebugViewOptions, glam::UVec4},
+    tap::prelude::*,
+};
+
+/// what the main pass shows instead of the shaded scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugView {
+    #[default]
+    Shaded,
+    /// needs [wgpu::Features::POLYGON_MODE_LINE]
+    Wireframe,
+    Normals,
+    TexCoords,
+    Depth,
+    /// how many lights each cluster of the clustered forward path holds
+    Clusters,
+    /// contribution of a single light source, by index
+    Light(u32),
+}
+
+impl DebugView {
+    /// the view after this one, going through every light before wrapping around
+    pub fn next(self, light_count: usize) -> Self {
+        match self {
+            DebugView::Shaded => DebugView::Wireframe,
+            DebugView::Wireframe => DebugView::Normals,
+            DebugView::Normals => DebugView::TexCoords,
+            DebugView::TexCoords => DebugView::Depth,
+            DebugView::Depth => DebugView::Clusters,
+            DebugView::Clusters => DebugView::Light(0),
+            DebugView::Light(index) => DebugView::Light(index + 1),
+        }
+        .pipe(|next| match next {
+            DebugView::Light(index) if index as usize >= light_count => DebugView::Shaded,
+            next => next,
+        })
+    }
+
+    pub fn material_features(self, features: MaterialFeatures) -> MaterialFeatures {
+        let fragment = match self {
+            DebugView::Shaded | DebugView::Wireframe => return features,
+            DebugView::Normals => "debug_view::debug_normals_fs",
+            DebugView::TexCoords => "debug_view::debug_tex_coords_fs",
+            DebugView::Depth => "debug_view::debug_depth_fs",
+            DebugView::Clusters => "debug_view::debug_clusters_fs",
+            DebugView::Light(_) => "debug_view::debug_light_fs",
+        };
+        MaterialFeatures {
+            entry_points: EntryPoints {
+                fragment,
+                ..features.entry_points
+            },
+            ..features
+        }
+    }
+
+    pub fn pass_features(self, features: PassFeatures) -> PassFeatures {
+        match self {
+            DebugView::Wireframe => PassFeatures {
+                polygon_mode: wgpu::PolygonMode::Line,
+                ..features
+            },
+            _ => features,
+        }
+    }
+
+    /// lights, cameras and bounds are outlined in every view but the shaded one
+    pub fn shows_gizmos(self) -> bool {
+        self != DebugView::Shaded
+    }
+
+    pub fn options(self) -> DebugViewOptions {
+        DebugViewOptions {
+            light_index: match self {
+                DebugView::Light(index) => UVec4::new(index, 0, 0, 0),
+                _ => UVec4::ZERO,
+            },
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use {
+    super::{
+        camera::{Camera, CameraPlugin},
+        light_source::LightSourcePlugin,
+        pipeline::{BlendMode, EntryPoints, MaterialFeatures, VertexLayout},
+        texture::Texture,
+        wgpu_ext::{
+            bind_group::{texture_entry, uniform_entry, HasBindGroup},
+            buffer::uniform::UniformBuffer,
+            global_context::device,
+        },
+    },
+    crate::bind_group_layout,
+    anyhow::{Context, Result},
+    shader_types::{deferred::GBufferView, Vec2},
+    tap::prelude::*,
+};
+
+/// fills the g-buffer in place of the fragment shader opaque materials use when shading forward
+pub const G_BUFFER_ENTRY_POINT: &str = "deferred::gbuffer_fs";
+/// twelve triangles for the cube around a light
+const LIGHT_VOLUME_VERTICES: u32 = 36;
+
+pub const LIGHT_VOLUME_MATERIAL: MaterialFeatures = MaterialFeatures {
+    blend_mode: BlendMode::Additive,
+    double_sided: false,
+    entry_points: EntryPoints {
+        vertex: "deferred::light_volume_vs",
+        fragment: "deferred::light_volume_fs",
+    },
+    vertex_layout: VertexLayout::LightVolume,
+};
+
+/// how the opaque part of the scene gets lit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Shading {
+    /// every fragment goes through every light source in `main_fs`
+    #[default]
+    Forward,
+    /// opaque surfaces land in the [GBuffer] first and are lit one light volume at a time, debug views stay forward
+    Deferred,
+}
+
+impl Shading {
+    pub fn toggle(self) -> Self {
+        match self {
+            Shading::Forward => Shading::Deferred,
+            Shading::Deferred => Shading::Forward,
+        }
+    }
+}
+
+/// albedo, normal and material of every opaque pixel, depth is shared with the regular depth texture
+pub struct GBuffer {
+    albedo: wgpu::TextureView,
+    normal: wgpu::TextureView,
+    material: wgpu::TextureView,
+    view: UniformBuffer<GBufferView>,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+bind_group_layout!(
+    GBuffer,
+    wgpu::BindGroupLayoutDescriptor {
+        label: struct_label!(),
+        entries: &[
+            // ALBEDO
+            texture_entry(0, wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false }),
+            // NORMAL
+            texture_entry(1, wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false }),
+            // MATERIAL
+            texture_entry(2, wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Float { filterable: false }),
+            // DEPTH
+            texture_entry(3, wgpu::ShaderStages::FRAGMENT, wgpu::TextureSampleType::Depth),
+            // VIEW
+            uniform_entry(4, wgpu::ShaderStages::FRAGMENT),
+        ],
+    }
+);
+
+impl GBuffer {
+    /// albedo, normal and material, in the order `gbuffer_fs` writes them after the surface
+    pub const FORMATS: [wgpu::TextureFormat; 3] = [
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+        wgpu::TextureFormat::Rgba16Float,
+        wgpu::TextureFormat::Rgba8Unorm,
+    ];
+
+    /// has to be rebuilt along with `depth` whenever the surface is resized
+    pub fn new((width, height): (u32, u32), depth: &Texture) -> Self {
+        let [albedo, normal, material] = Self::FORMATS.map(|format| {
+            device()
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: label!(format!("g-buffer {format:?}")),
+                    size: wgpu::Extent3d {
+                        width: width.max(1),
+                        height: height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        let view = UniformBuffer::new_init(&GBufferView::default());
+        device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: struct_label!(),
+                layout: Self::bind_group_layout(),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&albedo),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&normal),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&material),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&depth.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: view.as_ref().as_entire_binding(),
+                    },
+                ],
+            })
+            .pipe(|bind_group| Self {
+                albedo,
+                normal,
+                material,
+                view,
+                bind_group,
+            })
+    }
+
+    /// every g-buffer texture, cleared, to follow the surface in the g-buffer pass
+    pub fn color_attachments(&self) -> [Option<wgpu::RenderPassColorAttachment<'_>>; 3] {
+        [&self.albedo, &self.normal, &self.material].map(|view| {
+            Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })
+        })
+    }
+
+    /// lets the lighting pass reconstruct world positions from depth, `screen_size` is in pixels
+    pub async fn update(&self, camera: &Camera, screen_size: Vec2) -> Result<()> {
+        let view = GBufferView {
+            inverse_view_projection: camera.get_view_projection().inverse(),
+            screen_size: screen_size.extend(0.).extend(0.),
+        };
+        self.view
+            .write(0..1u64, move |buf| buf[0] = view)
+            .await
+            .context("writing g-buffer view")
+    }
+
+    /// one light volume per light source, added on top of the surface cleared by the g-buffer pass
+    pub fn draw_light_volumes(
+        &self,
+        pass: &mut wgpu::RenderPass<'_>,
+        pipeline: &wgpu::RenderPipeline,
+        camera_plugin: &CameraPlugin,
+        light_source_plugin: &LightSourcePlugin,
+    ) {
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &camera_plugin.bind_group, &[]);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        pass.set_bind_group(2, &light_source_plugin.bind_group, &[]);
+        pass.draw(0..LIGHT_VOLUME_VERTICES, 0..light_source_plugin.buffer.capacity() as u32);
+    }
+}
@@ -0,0 +1,58 @@
+use {
+    super::{camera::Camera, model::loader::LoadProgress, render_pass::RenderPass, wgpu_ext::profiler::PassTiming},
+    itertools::Itertools,
+    shader_types::{Color, Vec2},
+    std::time::Instant,
+};
+
+pub const HUD_TEXT_SIZE: f32 = 18.;
+const HUD_MARGIN: f32 = 8.;
+const HUD_COLOR: Color = Color([1., 1., 1., 1.]);
+/// how much of each new frame time goes into the average
+const FPS_SMOOTHING: f32 = 0.1;
+
+/// frames per second averaged over the last few calls to [FrameCounter::tick]
+pub struct FrameCounter {
+    last_frame: Instant,
+    fps: f32,
+}
+
+impl Default for FrameCounter {
+    fn default() -> Self {
+        Self {
+            last_frame: Instant::now(),
+            fps: 0.,
+        }
+    }
+}
+
+impl FrameCounter {
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let current = 1. / (now - self.last_frame).as_secs_f32().max(f32::EPSILON);
+        self.last_frame = now;
+        self.fps += (current - self.fps) * FPS_SMOOTHING;
+        self.fps
+    }
+}
+
+/// `timings` are only there while the gpu profiler is on
+pub fn draw_hud(pass: &mut RenderPass<'_, '_>, fps: f32, camera: &Camera, loading: Option<&LoadProgress>, timings: Option<&[PassTiming]>) {
+    let loading = loading
+        .map(|progress| {
+            let (done, total) = progress.steps();
+            format!("\nloading {:.0}% ({done}/{total})", progress.fraction() * 100.)
+        })
+        .unwrap_or_default();
+    let timings = timings
+        .unwrap_or_default()
+        .iter()
+        .map(|PassTiming { label, duration }| format!("\n{label}: {:.2} ms", duration.as_secs_f64() * 1000.))
+        .join("");
+    pass.text(
+        Vec2::splat(HUD_MARGIN),
+        HUD_TEXT_SIZE,
+        HUD_COLOR,
+        &format!("{fps:.0} fps\n{:.2}{loading}{timings}", camera.position()),
+    );
+}
@@ -1,6 +1,9 @@
 use {
     derivative::Derivative,
-    std::{rc::Rc, sync::atomic::AtomicU16},
+    std::{
+        rc::{Rc, Weak},
+        sync::atomic::AtomicU16,
+    },
 };
 
 pub static ID_COUNTER: AtomicU16 = AtomicU16::new(0);
@@ -46,6 +49,34 @@ impl<T> WithId<T> {
     }
 }
 
+impl<T> WithId<T> {
+    /// a handle that doesn't keep `T` alive, for caches
+    pub fn downgrade(&self) -> WeakWithId<T> {
+        WeakWithId {
+            inner: Rc::downgrade(&self.inner),
+            id: self.id,
+        }
+    }
+}
+
+/// see [WithId::downgrade], upgrading hands back the same id
+pub struct WeakWithId<T> {
+    inner: Weak<T>,
+    id: Id,
+}
+
+impl<T> WeakWithId<T> {
+    pub fn upgrade(&self) -> Option<WithId<T>> {
+        self.inner
+            .upgrade()
+            .map(|inner| WithId { inner, id: self.id })
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.inner.strong_count() > 0
+    }
+}
+
 impl<T> AsRef<T> for WithId<T> {
     fn as_ref(&self) -> &T {
         &self.inner
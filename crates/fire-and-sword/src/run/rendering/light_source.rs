@@ -1,43 +1,153 @@
 use {
-    super::wgpu_ext::{bind_group::HasBindGroup, buffer::storage::StorageBuffer, global_context::device},
+    super::{
+        camera::Camera,
+        pipeline::PipelineCache,
+        wgpu_ext::{
+            bind_group::{storage_entry, uniform_entry, HasBindGroup},
+            buffer::{storage::StorageBuffer, uniform::UniformBuffer},
+            compute::{workgroups, ComputeEncoderExt},
+            global_context::device,
+            profiler::GpuProfiler,
+        },
+    },
     crate::bind_group_layout,
-    shader_types::light_source::LightSource,
-    tap::prelude::*,
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    shader_types::{
+        cluster::{ClusterView, CLUSTER_COUNT, CLUSTER_STRIDE, CLUSTER_WORKGROUP_SIZE},
+        light_source::LightSource,
+        Vec2,
+    },
+    tracing::info,
 };
 
+pub const CLUSTER_ENTRY_POINT: &str = "cluster::cluster_lights_cs";
+
 pub struct LightSourcePlugin {
     pub buffer: StorageBuffer<LightSource>,
+    cluster_view: UniformBuffer<ClusterView>,
     pub bind_group: wgpu::BindGroup,
+    cluster_bind_group: wgpu::BindGroup,
+    // for every cluster, how many lights reach it followed by their indices
+    clusters: StorageBuffer<u32>,
 }
 bind_group_layout!(
     LightSourcePlugin,
     wgpu::BindGroupLayoutDescriptor {
         label: struct_label!(),
-        entries: &[wgpu::BindGroupLayoutEntry {
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Storage { read_only: true },
-                has_dynamic_offset: false,
-                min_binding_size: None
-            },
-            binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
-            count: None
-        }]
+        entries: &[
+            // LIGHT SOURCES, light volumes are placed in the vertex shader
+            storage_entry(0, wgpu::ShaderStages::VERTEX_FRAGMENT, true),
+            // CLUSTERS
+            storage_entry(1, wgpu::ShaderStages::FRAGMENT, true),
+            // CLUSTER VIEW
+            uniform_entry(2, wgpu::ShaderStages::FRAGMENT),
+        ]
+    }
+);
+
+/// assigns every light source to the clusters of the view frustum it reaches, ahead of anything drawn with `main_fs`
+pub struct LightClusters;
+
+bind_group_layout!(
+    LightClusters,
+    CLUSTER_BIND_GROUP,
+    wgpu::BindGroupLayoutDescriptor {
+        label: struct_label!(),
+        entries: &[
+            // LIGHT SOURCES
+            storage_entry(0, wgpu::ShaderStages::COMPUTE, true),
+            // CLUSTERS
+            storage_entry(1, wgpu::ShaderStages::COMPUTE, false),
+            // CLUSTER VIEW
+            uniform_entry(2, wgpu::ShaderStages::COMPUTE),
+        ]
     }
 );
 
 impl LightSourcePlugin {
     pub fn new(init: &[LightSource]) -> Self {
-        StorageBuffer::new_init(init).pipe(|buffer| Self {
-            bind_group: device().create_bind_group(&wgpu::BindGroupDescriptor {
+        let buffer = StorageBuffer::new_init(init);
+        let clusters = StorageBuffer::<u32>::new_empty((CLUSTER_COUNT * CLUSTER_STRIDE) as _);
+        let cluster_view = UniformBuffer::new_init(&ClusterView::default());
+        let bind_group = |layout| {
+            device().create_bind_group(&wgpu::BindGroupDescriptor {
                 label: struct_label!(),
-                layout: Self::bind_group_layout(),
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: buffer.as_ref().as_entire_binding(),
-                }],
-            }),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_ref().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: clusters.as_ref().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: cluster_view.as_ref().as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        Self {
+            bind_group: bind_group(Self::bind_group_layout()),
+            cluster_bind_group: bind_group(LightClusters::bind_group_layout()),
             buffer,
-        })
+            cluster_view,
+            clusters,
+        }
+    }
+
+    /// has to match the camera the frame is drawn with, `screen_size` is in pixels
+    pub async fn update_clusters(&self, camera: &Camera, screen_size: Vec2) -> Result<()> {
+        let cluster_view = ClusterView {
+            view: camera.view(),
+            inverse_projection: camera.projection().inverse(),
+            screen_size: screen_size.extend(0.).extend(0.),
+        };
+        self.cluster_view
+            .write(0..1u64, move |buf| buf[0] = cluster_view)
+            .await
+            .context("writing cluster view")
+    }
+
+    /// reads back the assignment of the last frame and logs how many lights reach the clusters
+    pub async fn dump_clusters(&self) -> Result<()> {
+        let ClusterView { screen_size, .. } = self
+            .cluster_view
+            .read(0..1)
+            .await
+            .context("reading cluster view")?[0];
+        let counts = self
+            .clusters
+            .read(0..(CLUSTER_COUNT * CLUSTER_STRIDE) as u64)
+            .await
+            .context("reading clusters")?
+            .into_iter()
+            .step_by(CLUSTER_STRIDE as usize)
+            .collect_vec();
+        info!(
+            "[{}] of [{CLUSTER_COUNT}] clusters are lit for a {}x{} view, at most [{}] lights reach one",
+            counts.iter().filter(|count| **count > 0).count(),
+            screen_size.x,
+            screen_size.y,
+            counts.iter().max().copied().unwrap_or_default()
+        );
+        Ok(())
+    }
+
+    /// records the light assignment into the frame, ahead of the passes reading it
+    pub fn assign_clusters(&self, encoder: &mut wgpu::CommandEncoder, pipelines: &mut PipelineCache, profiler: Option<&mut GpuProfiler>) {
+        let timestamp_writes = profiler.and_then(|profiler| profiler.compute_pass_writes("light clusters"));
+        encoder.compute_pass(
+            "light clusters",
+            pipelines.get_or_create_compute(CLUSTER_ENTRY_POINT),
+            timestamp_writes,
+            |pass| {
+                pass.set_bind_group(0, &self.cluster_bind_group, &[]);
+                pass.dispatch_workgroups(workgroups(CLUSTER_COUNT, CLUSTER_WORKGROUP_SIZE), 1, 1);
+            },
+        )
     }
 }
@@ -11,6 +11,8 @@ pub mod mesh;
 
 pub mod load_gltf;
 pub mod load_obj;
+pub mod loader;
+pub mod lod;
 
 #[extension_traits::extension(pub trait RenderPassDrawModelExt)]
 impl<'a> RenderPass<'a> {
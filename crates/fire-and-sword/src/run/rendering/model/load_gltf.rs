@@ -1,134 +1,401 @@
 use {
-    super::{material::MaterialPlugin, mesh::MeshPlugin, Primitive},
-    crate::run::rendering::{identify::WithId, texture::Texture},
+    super::{
+        loader::LoadProgress,
+        lod::{msft_lod_meshes, simplified_levels, Lod, LodSelection, GENERATED_LODS},
+        material::MaterialPlugin,
+        mesh::{generate_normals, triangle_list, MeshPlugin},
+        Primitive,
+    },
+    crate::run::rendering::{
+        assets::{self, AssetIndex, AssetKey},
+        pipeline::{BlendMode, MaterialFeatures},
+        texture::Texture,
+    },
     anyhow::{Context, Result},
-    gltf::image::Source,
-    image::{GenericImage, GenericImageView, Rgba},
+    gltf::image::Format,
+    image::{DynamicImage, ImageBuffer},
     itertools::Itertools,
     nonempty::NonEmpty,
-    shader_types::{model::ModelVertex, padding::pad, Vec2, Vec3},
+    shader_types::{material::MaterialFactors, model::ModelVertex, padding::pad, Vec2, Vec3, Vec4},
+    std::path::{Path, PathBuf},
     tap::prelude::*,
 };
 
-pub type GltfImport = (gltf::Document, Vec<gltf::buffer::Data>, Vec<gltf::image::Data>);
+/// vertices and indices of a primitive, most detailed level first
+pub type MeshLevels = NonEmpty<(Vec<ModelVertex>, Vec<u32>)>;
+
+/// a glTF file along with every buffer and image it refers to
+pub struct GltfImport {
+    pub document: gltf::Document,
+    pub buffers: Vec<gltf::buffer::Data>,
+    pub images: Vec<gltf::image::Data>,
+    /// keys every asset loaded from the file, see [crate::run::rendering::assets]
+    pub source: PathBuf,
+    /// by mesh and primitive index, waiting to be uploaded, [None] for primitives that aren't made of triangles
+    pub meshes: Vec<Vec<Option<MeshLevels>>>,
+}
+
+/// `.gltf` files pull in their buffers and images relative to `path`, `data:` uris are decoded in place
+///
+/// every image gets decoded and every mesh built in here, uploading them is left to the render thread
+pub fn import_file(path: &Path, progress: &LoadProgress) -> Result<GltfImport> {
+    progress.add_work(1);
+    let (document, buffers, images) = gltf::import(path).with_context(|| format!("importing [{}]", path.display()))?;
+    progress.finish_one();
+    progress.add_work(document.meshes().map(|mesh| mesh.primitives().len()).sum());
+    let own_lods = msft_lod_meshes(&document);
+    document
+        .meshes()
+        .map(|mesh| {
+            let generated = match own_lods.contains(&mesh.index()) {
+                true => [].as_slice(),
+                false => GENERATED_LODS.as_slice(),
+            };
+            mesh.primitives()
+                .map(|primitive| {
+                    let index = primitive.index();
+                    Primitive::build_levels(&buffers, &mesh, primitive, generated)
+                        .with_context(|| format!("building primitive #{index} of mesh #{}", mesh.index()))
+                        .tap_ok(|_| progress.finish_one())
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(|meshes| GltfImport {
+            document,
+            buffers,
+            images,
+            source: path.to_owned(),
+            meshes,
+        })
+}
+
+impl GltfImport {
+    /// the file itself and every buffer and image it refers to by path, `data:` uris live in the file
+    pub fn sources(&self) -> Vec<PathBuf> {
+        let directory = self.source.parent().unwrap_or(Path::new("."));
+        self.document
+            .buffers()
+            .filter_map(|buffer| match buffer.source() {
+                gltf::buffer::Source::Uri(uri) => Some(uri),
+                gltf::buffer::Source::Bin => None,
+            })
+            .chain(
+                self.document
+                    .images()
+                    .filter_map(|image| match image.source() {
+                        gltf::image::Source::Uri { uri, .. } => Some(uri),
+                        gltf::image::Source::View { .. } => None,
+                    }),
+            )
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| directory.join(uri))
+            .pipe(|dependencies| std::iter::once(self.source.clone()).chain(dependencies))
+            .unique()
+            .collect()
+    }
+}
+
+/// undoes the conversion [gltf::import] puts every image through
+fn decode_image(gltf::image::Data { pixels, format, width, height }: &gltf::image::Data) -> Result<DynamicImage> {
+    let (width, height) = (*width, *height);
+    let wide = || {
+        pixels
+            .chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+            .collect_vec()
+    };
+    let float = || {
+        pixels
+            .chunks_exact(4)
+            .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+            .collect_vec()
+    };
+    match format {
+        Format::R8 => ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageRgba8),
+        Format::R16 => ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageLuma16),
+        Format::R16G16 => ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageLumaA16),
+        Format::R16G16B16 => ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageRgb16),
+        Format::R16G16B16A16 => ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageRgba16),
+        Format::R32G32B32FLOAT => ImageBuffer::from_raw(width, height, float()).map(DynamicImage::ImageRgb32F),
+        Format::R32G32B32A32FLOAT => ImageBuffer::from_raw(width, height, float()).map(DynamicImage::ImageRgba32F),
+    }
+    .with_context(|| format!("[{}] bytes of {format:?} do not fill {width}x{height}", pixels.len()))
+}
+
+/// axis aligned, in the space of the mesh
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Model {
     pub primitives: NonEmpty<Primitive>,
+    pub bounds: Bounds,
+    /// most detailed first, `primitives` is drawn until the first of them takes over
+    pub lods: Vec<Lod>,
+    pub(crate) lod_selection: LodSelection,
 }
 
 impl Model {
     pub fn load_all(context: &GltfImport) -> Result<Option<NonEmpty<Self>>> {
         context
-            .0
+            .document
             .meshes()
             .enumerate()
-            .map(|(idx, m)| Self::load(context, m).with_context(|| format!("loading mesh #{idx}")))
+            .filter_map(|(idx, m)| {
+                Self::load(context, m)
+                    .with_context(|| format!("loading mesh #{idx}"))
+                    .transpose()
+            })
             .collect::<Result<_>>()
             .map(NonEmpty::from_vec)
     }
-    pub fn load(context: &GltfImport, mesh: gltf::Mesh<'_>) -> Result<Self> {
+    /// [None] when none of the primitives of the mesh are made of triangles
+    pub fn load(context: &GltfImport, mesh: gltf::Mesh<'_>) -> Result<Option<Self>> {
+        Self::load_levels(context, mesh, &GENERATED_LODS)
+    }
+
+    /// one level past full detail per entry of `generated`, primitives running out of levels early keep their last one
+    pub(super) fn load_levels(context: &GltfImport, mesh: gltf::Mesh<'_>, generated: &[(u32, f32)]) -> Result<Option<Self>> {
         mesh.primitives()
-            .map(|primitive| Primitive::load_primitive(context, primitive))
+            .filter_map(|primitive| {
+                Primitive::load_levels(context, mesh.index(), primitive.clone(), generated.len() + 1)
+                    .map(|loaded| {
+                        loaded.map(|loaded| {
+                            primitive
+                                .bounding_box()
+                                .pipe(|gltf::mesh::Bounds { min, max }| Bounds {
+                                    min: Vec3::from(min),
+                                    max: Vec3::from(max),
+                                })
+                                .pipe(|bounds| (loaded, bounds))
+                        })
+                    })
+                    .transpose()
+            })
             .collect::<Result<Vec<_>>>()
             .context("not all primitives could be loaded")
-            .and_then(|v| NonEmpty::from_vec(v).context("model cannot be empty"))
-            .map(|primitives| Self { primitives })
+            .map(|v| NonEmpty::from_vec(v).map(|loaded| Self::from_levels(loaded, generated)))
+    }
+
+    /// `loaded` holds every primitive with its levels, `generated` the thresholds the levels past the first were made for
+    pub(super) fn from_levels(loaded: NonEmpty<(NonEmpty<Primitive>, Bounds)>, generated: &[(u32, f32)]) -> Self {
+        Self {
+            bounds: loaded
+                .tail
+                .iter()
+                .fold(loaded.head.1, |acc, (_, bounds)| acc.union(*bounds)),
+            lods: (1..loaded
+                .iter()
+                .map(|(levels, _)| levels.len())
+                .max()
+                .unwrap_or(1))
+                .map(|level| Lod {
+                    primitives: loaded
+                        .clone()
+                        .map(|(levels, _)| levels.get(level).unwrap_or(levels.last()).clone()),
+                    coverage: generated[level - 1].1,
+                })
+                .collect(),
+            lod_selection: LodSelection::default(),
+            primitives: loaded.map(|(levels, _)| levels.head),
+        }
+    }
+}
+
+impl MaterialFeatures {
+    pub fn from_gltf(material: &gltf::Material<'_>) -> Self {
+        Self {
+            blend_mode: match material.alpha_mode() {
+                gltf::material::AlphaMode::Blend => BlendMode::AlphaBlend,
+                // TODO: alpha cutoff needs a discarding fragment entry point
+                gltf::material::AlphaMode::Opaque | gltf::material::AlphaMode::Mask => BlendMode::Opaque,
+            },
+            double_sided: material.double_sided(),
+            ..Default::default()
+        }
     }
 }
 
 impl Primitive {
-    pub fn load_primitive((document, buffer_data, image_data): &GltfImport, primitive: gltf::Primitive<'_>) -> Result<Self> {
-        primitive
-            .reader(|buffer| {
-                buffer_data
-                    .get(buffer.index())
-                    .map(|v| v.as_ref())
-                    .tap_none(|| tracing::warn!("no buffer found in data at index [{}]", buffer.index()))
+    /// uploads up to `levels` of the levels [import_file] built for the primitive of mesh `mesh`, all of them share the material
+    ///
+    /// meshes, materials and textures come from [assets], so anything loaded before is shared rather than uploaded again
+    ///
+    /// [None] for primitives [import_file] skipped
+    pub fn load_levels(
+        GltfImport {
+            document,
+            images,
+            source,
+            meshes,
+            ..
+        }: &GltfImport,
+        mesh: usize,
+        primitive: gltf::Primitive<'_>,
+        levels: usize,
+    ) -> Result<Option<NonEmpty<Self>>> {
+        let Some(prepared) = meshes
+            .get(mesh)
+            .and_then(|primitives| primitives.get(primitive.index()))
+            .context("primitive was never built")?
+        else {
+            return Ok(None);
+        };
+        prepared
+            .iter()
+            .take(levels.max(1))
+            .enumerate()
+            .map(|(level, (vertices, indices))| {
+                assets::mesh(
+                    AssetKey::new(
+                        source,
+                        AssetIndex::Mesh {
+                            mesh,
+                            primitive: primitive.index(),
+                            level,
+                        },
+                    ),
+                    || Ok(MeshPlugin::load_mesh(vertices, indices)),
+                )
             })
-            .pipe(|reader| {
-                reader
-                    .read_positions()
-                    .context("no positions found")
-                    .and_then(|positions| {
-                        reader
-                            .read_normals()
-                            .context("no normals")
-                            .and_then(|normals| {
-                                reader
-                                    .read_tex_coords(0)
-                                    .with_context(|| "no text coords at set [0]".to_string())
-                                    .map(|tex_coords| itertools::multizip((positions, normals, tex_coords.into_f32())))
-                            })
-                    })
-                    .and_then(|vertices| {
-                        reader.read_indices().context("indices").map(|indices| {
-                            vertices
-                                .map(|(position, normal, tex_coords)| ModelVertex {
-                                    position: Vec3::from(position).extend(1.),
-                                    normal: Vec3::from(normal).extend(1.),
-                                    tex_coords: Vec2::from(tex_coords),
-                                    padding: pad(()),
+            .collect::<Result<Vec<_>>>()
+            .map(NonEmpty::from_vec)?
+            .context("levels start out with the primitive itself")
+            .and_then(|meshes| {
+                primitive
+                    .material()
+                    .pipe(|material| {
+                        assets::material(AssetKey::new(source, AssetIndex::Material(material.index())), || {
+                            let features = MaterialFeatures::from_gltf(&material);
+                            let pbr = material.pbr_metallic_roughness();
+                            let factors = MaterialFactors {
+                                base_color: Vec4::from_array(pbr.base_color_factor()),
+                                specular: material
+                                    .specular()
+                                    .map(|specular| specular.specular_factor())
+                                    .unwrap_or(1.)
+                                    .pipe(|specular| Vec4::new(specular, 0., 0., 0.)),
+                            };
+                            pbr.base_color_texture()
+                                .map(|info| {
+                                    document
+                                        .textures()
+                                        .nth(info.texture().index())
+                                        .with_context(|| format!("no texture at index [{}]", info.texture().index()))
+                                        .and_then(|texture| {
+                                            let image = texture.source().index();
+                                            // decoded by the import already, embedded, external and data uri images alike
+                                            assets::texture(AssetKey::new(source, AssetIndex::Image(image)), || {
+                                                images
+                                                    .get(image)
+                                                    .with_context(|| format!("no image data at index [{image}]"))
+                                                    .and_then(decode_image)
+                                                    .map(|decoded| Texture::from_image(&decoded, texture.name()))
+                                            })
+                                            .map(|data| MaterialPlugin::load(texture.name().unwrap_or("UNKNOWN"), data, factors, features))
+                                        })
                                 })
-                                .collect_vec()
-                                .pipe_deref(|vertices| MeshPlugin::load_mesh(vertices, indices.into_u32().collect_vec().as_slice()))
+                                .unwrap_or_else(|| Ok(MaterialPlugin::load("BASE", assets::white(), factors, features)))
                         })
                     })
-                    .and_then(|mesh| {
-                        primitive
-                            .material()
-                            .pipe(|material| {
-                                material.pbr_metallic_roughness().pipe(|pbr| {
-                                    pbr.base_color_texture()
-                                        .map(|info| {
-                                            document
-                                                .textures()
-                                                .nth(info.texture().index())
-                                                .with_context(|| format!("no texture at index [{}]", info.texture().index()))
-                                                .and_then(|texture| {
-                                                    match texture.source().source() {
-                                                        Source::View { view, mime_type: _ } => {
-                                                            let start = view.offset();
-                                                            let end = view.offset() + view.length();
-                                                            buffer_data
-                                                                .get(view.buffer().index())
-                                                                .context("bad index")
-                                                                .and_then(|data| data.get(start..end).context("bad data slice"))
-                                                                .and_then(|data| Texture::from_bytes(data, texture.name().unwrap_or("UNKNOWN")))
-                                                        }
-                                                        Source::Uri { uri: _, mime_type: _ } => anyhow::bail!("Source::Uri {{ uri: _, mime_type: _ }}"),
-                                                    }
-                                                    .map(|data| MaterialPlugin::load(texture.name().unwrap_or("UNKNOWN"), data))
-                                                })
-                                        })
-                                        .unwrap_or_else(|| {
-                                            image::DynamicImage::new(32, 32, image::ColorType::Rgba8)
-                                                .tap_mut(|image| {
-                                                    image
-                                                        .pixels()
-                                                        .map(|(x, y, _)| (x, y))
-                                                        .collect_vec()
-                                                        .pipe(|pixels| {
-                                                            pixels.into_iter().for_each(|(x, y)| {
-                                                                image.put_pixel(x, y, Rgba(pbr.base_color_factor().map(|c| (c * u8::MAX as f32) as u8)))
-                                                            })
-                                                        })
-                                                })
-                                                .pipe(|image| Texture::from_image(&image, "BASE".into()))
-                                                .pipe(|texture| MaterialPlugin::load("BASE", texture))
-                                                .pipe(Ok)
-                                        })
-                                })
-                            })
-                            .map(
-                                #[allow(deprecated)]
-                                {
-                                    |material| (WithId::register(mesh), WithId::register(material))
-                                },
-                            )
-                            .map(|(mesh, material)| Primitive { mesh, material })
+                    .map(|material| {
+                        meshes.map(|mesh| Primitive {
+                            mesh,
+                            material: material.clone(),
+                        })
                     })
             })
+            .map(Some)
+    }
+
+    /// reads the vertices of `primitive` and simplifies them into `generated`, nothing in here touches the gpu
+    ///
+    /// missing indices, normals and texture coordinates are made up with a warning, strips and fans become lists and anything else is skipped
+    fn build_levels(
+        buffers: &[gltf::buffer::Data],
+        mesh: &gltf::Mesh<'_>,
+        primitive: gltf::Primitive<'_>,
+        generated: &[(u32, f32)],
+    ) -> Result<Option<MeshLevels>> {
+        let name = format!("{} #{}", mesh.name().unwrap_or("(unnamed)"), mesh.index());
+        let reader = primitive.reader(|buffer| {
+            buffers
+                .get(buffer.index())
+                .map(|v| v.as_ref())
+                .tap_none(|| tracing::warn!("no buffer found in data at index [{}]", buffer.index()))
+        });
+        let positions = reader
+            .read_positions()
+            .context("no positions found")?
+            .map(Vec3::from)
+            .collect_vec();
+        let Some(indices) = reader
+            .read_indices()
+            .map(|indices| indices.into_u32().collect_vec())
+            .unwrap_or_else(|| {
+                tracing::warn!("mesh [{name}] has no indices, drawing its vertices in order");
+                (0..positions.len() as u32).collect_vec()
+            })
+            .pipe(|indices| triangle_list(&name, primitive.mode(), indices))
+        else {
+            return Ok(None);
+        };
+        let normals = match reader.read_normals() {
+            Some(normals) => normals.map(Vec3::from).collect_vec(),
+            None => {
+                tracing::warn!("mesh [{name}] has no normals, generating them from its faces");
+                generate_normals(&positions, &indices)
+            }
+        };
+        let tex_coords = match reader.read_tex_coords(0) {
+            Some(tex_coords) => tex_coords.into_f32().map(Vec2::from).collect_vec(),
+            None => {
+                tracing::warn!("mesh [{name}] has no texture coordinates at set [0], mapping all of it to the corner of its texture");
+                vec![Vec2::ZERO; positions.len()]
+            }
+        };
+        // weights of zero leave a vertex where it is, so meshes without a skin can share the shader
+        let skin = match (reader.read_joints(0), reader.read_weights(0)) {
+            (Some(joints), Some(weights)) => joints
+                .into_u16()
+                .map(|joints| Vec4::from_array(joints.map(f32::from)))
+                .zip(weights.into_f32().map(Vec4::from_array))
+                .collect_vec(),
+            (None, None) => vec![(Vec4::ZERO, Vec4::ZERO); positions.len()],
+            _ => {
+                tracing::warn!("mesh [{name}] has only one of JOINTS_0 and WEIGHTS_0, leaving it unskinned");
+                vec![(Vec4::ZERO, Vec4::ZERO); positions.len()]
+            }
+        };
+        itertools::multizip((positions, normals, tex_coords, skin))
+            .map(|(position, normal, tex_coords, (joints, weights))| ModelVertex {
+                position: position.extend(1.),
+                normal: normal.extend(1.),
+                tex_coords,
+                padding: pad(()),
+                joints,
+                weights,
+            })
+            .collect_vec()
+            .pipe(|vertices| simplified_levels(vertices, indices, &generated.iter().map(|(cells, _)| *cells).collect_vec()))
+            .pipe(Some)
+            .pipe(Ok)
     }
     // pub fn load_gltf_bytes(gltf_bytes: &[u8]) -> Result<Self> {
     //     gltf::import_slice(gltf_bytes)
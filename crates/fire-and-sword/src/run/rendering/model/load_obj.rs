@@ -1,113 +1,291 @@
-// use {
-//     crate::run::rendering::texture::Texture,
-//     anyhow::{Context, Result},
-//     std::path::Path,
-// };
+use {
+    super::{
+        load_gltf::{Bounds, MeshLevels, Model},
+        loader::LoadProgress,
+        lod::{simplified_levels, GENERATED_LODS},
+        material::MaterialPlugin,
+        mesh::{generate_normals, MeshPlugin},
+        Primitive,
+    },
+    crate::run::rendering::{
+        assets::{self, AssetIndex, AssetKey},
+        pipeline::{BlendMode, MaterialFeatures},
+        texture::Texture,
+    },
+    anyhow::{Context, Result},
+    image::DynamicImage,
+    itertools::Itertools,
+    nonempty::NonEmpty,
+    shader_types::{material::MaterialFactors, model::ModelVertex, padding::pad, Vec2, Vec3, Vec4},
+    std::path::{Path, PathBuf},
+    tap::prelude::*,
+};
 
-// fn assets_root() -> &'static Path {
-//     Path::new("assets")
-// }
+/// what blender writes for a material nobody touched, used for objects without one
+const DEFAULT_DIFFUSE: [f32; 3] = [0.8, 0.8, 0.8];
 
-// pub fn load_texture(file_name: &Path) -> Result<Texture> {
-//     let data = std::fs::read(file_name).with_context(|| format!("loading file at [{file_name:?}]"))?;
-//     Texture::from_bytes(&data, &file_name.display().to_string())
-// }
+/// an `.obj` file parsed, its textures decoded and its objects simplified, nothing in it has touched the gpu yet
+pub struct PreparedObj {
+    path: PathBuf,
+    /// the `.mtl` libraries and textures that were read along with it
+    dependencies: Vec<PathBuf>,
+    objects: Vec<PreparedObject>,
+    materials: Vec<PreparedMaterial>,
+}
 
-// // impl Primitive {
-// //     /// this is absolutely horrible but it's for tutorial so whatever
-// //     /// again I hate this and I pledge to get rid of this on first
-// //     /// occasion
-// //     pub fn load_obj_learn_wgpu_way(file_name: &str) -> Result<Self> {
-// //         let file_name = assets_root().join(file_name);
-// //         let obj_text = read_to_string(&file_name).with_context(|| format!("loading text from [{file_name:?}]"))?;
-// //         let obj_cursor = Cursor::new(obj_text);
-// //         let mut obj_reader = BufReader::new(obj_cursor);
+struct PreparedObject {
+    levels: MeshLevels,
+    bounds: Bounds,
+    material: Option<usize>,
+}
 
-// //         let (models, obj_materials) = tobj::load_obj_buf(
-// //             &mut obj_reader,
-// //             &tobj::LoadOptions {
-// //                 triangulate: true,
-// //                 single_index: true,
-// //                 ..Default::default()
-// //             },
-// //             |p| {
-// //                 let mat_text = read_to_string(assets_root().join(p)).unwrap();
-// //                 tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
-// //             },
-// //         )?;
+struct PreparedMaterial {
+    name: String,
+    features: MaterialFeatures,
+    factors: MaterialFactors,
+    /// the diffuse map and where it was read from, [assets::white] stands in for materials without one
+    texture: Option<(PathBuf, DynamicImage)>,
+}
 
-// //         let mut materials = Vec::new();
-// //         for m in obj_materials? {
-// //             let diffuse_texture = m
-// //                 .diffuse_texture
-// //                 .context("no diffuse texture name")
-// //                 .map(|texture| assets_root().join(texture))
-// //                 .and_then(|path| load_texture(&path).context("loading texture"))?;
-// //             let material = MaterialPlugin::load(&m.name, diffuse_texture);
+impl PreparedObj {
+    /// every object in the file becomes a primitive, the `.mtl` and its textures are looked up next to `path`
+    pub fn prepare(path: &Path, progress: &LoadProgress) -> Result<Self> {
+        let directory = path.parent().unwrap_or(Path::new("."));
+        progress.add_work(1);
+        let (objects, materials) = tobj::load_obj(
+            path,
+            &tobj::LoadOptions {
+                triangulate: true,
+                single_index: true,
+                ..Default::default()
+            },
+        )
+        .with_context(|| format!("parsing [{}]", path.display()))?;
+        let materials = materials
+            .tap_err(|e| tracing::warn!("no materials for [{}], falling back to the default: {e}", path.display()))
+            .unwrap_or_default();
+        progress.finish_one();
+        progress.add_work(materials.len() + objects.len());
 
-// //             materials.push(material)
-// //         }
-// //         // HACK
-// //         materials.push(load_texture(Path::new("assets/cube-diffuse.jpg")).map(|texture| MaterialPlugin::load("cube-diffuse.jpg", texture))?);
-// //         let materials = NonEmpty::from_vec(materials).context("empty materials?")?;
-// //         let meshes = models
-// //             .into_iter()
-// //             .map(|m| {
-// //                 let vertices = (0..m.mesh.positions.len() / 3)
-// //                     .map(|i| {
-// //                         let normal = if m.mesh.normals.is_empty() {
-// //                             // Assume every 3 vertices form a triangle, and i is the vertex index
-// //                             let tri_idx = i - (i % 3); // Round down to the start of the triangle
-// //                             let v0 = Vec3::new(
-// //                                 m.mesh.positions[(tri_idx * 3) % m.mesh.positions.len()],
-// //                                 m.mesh.positions[(tri_idx * 3 + 1) % m.mesh.positions.len()],
-// //                                 m.mesh.positions[(tri_idx * 3 + 2) % m.mesh.positions.len()],
-// //                             );
-// //                             let v1 = Vec3::new(
-// //                                 m.mesh.positions[(tri_idx * 3 + 3) % m.mesh.positions.len()],
-// //                                 m.mesh.positions[(tri_idx * 3 + 4) % m.mesh.positions.len()],
-// //                                 m.mesh.positions[(tri_idx * 3 + 5) % m.mesh.positions.len()],
-// //                             );
-// //                             let v2 = Vec3::new(
-// //                                 m.mesh.positions[(tri_idx * 3 + 6) % m.mesh.positions.len()],
-// //                                 m.mesh.positions[(tri_idx * 3 + 7) % m.mesh.positions.len()],
-// //                                 m.mesh.positions[(tri_idx * 3 + 8) % m.mesh.positions.len()],
-// //                             );
-// //                             // Compute edges
-// //                             let edge1 = v1 - v0;
-// //                             let edge2 = v2 - v0;
-// //                             // Cross product for normal
-// //                             let n = edge1.cross(edge2).normalize_or_zero(); // Normalize, handle degenerate case
-// //                             Vec4::new(n.x, n.y, n.z, 0.0)
-// //                         } else {
-// //                             Vec4::new(m.mesh.normals[i * 3], m.mesh.normals[i * 3 + 1], m.mesh.normals[i * 3 + 2], 0.0)
-// //                         };
+        let materials = materials
+            .iter()
+            .map(|material| prepare_material(directory, material).tap(|_| progress.finish_one()))
+            .collect_vec();
+        let cells = GENERATED_LODS.map(|(cells, _)| cells);
+        let objects = objects
+            .into_iter()
+            .map(|tobj::Model { mesh, .. }| {
+                let vertices = vertices(&mesh);
+                let bounds = vertices.iter().fold(
+                    Bounds {
+                        min: Vec3::MAX,
+                        max: Vec3::MIN,
+                    },
+                    |bounds, vertex| Bounds {
+                        min: bounds.min.min(vertex.position.truncate()),
+                        max: bounds.max.max(vertex.position.truncate()),
+                    },
+                );
+                PreparedObject {
+                    levels: simplified_levels(vertices, mesh.indices, &cells),
+                    bounds,
+                    material: mesh.material_id,
+                }
+                .tap(|_| progress.finish_one())
+            })
+            .collect_vec();
+        Ok(Self {
+            path: path.to_owned(),
+            dependencies: material_libraries(path)
+                .into_iter()
+                .chain(
+                    materials
+                        .iter()
+                        .filter_map(|material| material.texture.as_ref().map(|(path, _)| path.clone())),
+                )
+                .collect(),
+            objects,
+            materials,
+        })
+    }
 
-// //                         if m.mesh.texcoords.is_empty() {
-// //                             ModelVertex {
-// //                                 position: Vec4::new(m.mesh.positions[i * 3], m.mesh.positions[i * 3 + 1], m.mesh.positions[i * 3 + 2], 0.0),
-// //                                 tex_coords: Vec2::new(0.0, 0.0), // Default texture coordinates
-// //                                 normal,
-// //                                 padding: pad(()),
-// //                             }
-// //                         } else {
-// //                             ModelVertex {
-// //                                 position: Vec4::new(m.mesh.positions[i * 3], m.mesh.positions[i * 3 + 1], m.mesh.positions[i * 3 + 2], 0.0),
-// //                                 tex_coords: Vec2::new(m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]),
-// //                                 normal,
-// //                                 padding: pad(()),
-// //                             }
-// //                         }
-// //                     })
-// //                     .collect::<Vec<_>>();
-// //                 MeshPlugin::load_mesh(&vertices, &m.mesh.indices)
-// //             })
-// //             .pipe(NonEmpty::collect)
-// //             .context("empty meshes")?;
+    /// the file itself first
+    pub fn sources(&self) -> Vec<PathBuf> {
+        std::iter::once(self.path.clone())
+            .chain(self.dependencies.iter().cloned())
+            .collect()
+    }
 
-// //         Ok(Primitive {
-// //             mesh: meshes,
-// //             material: materials,
-// //         })
-// //     }
-// // }
+    /// has to run on the render thread, assets loaded from the same file before are reused
+    pub fn load(self) -> Result<Model> {
+        let Self { path, objects, materials, .. } = self;
+        let materials = materials
+            .into_iter()
+            .enumerate()
+            .map(|(idx, material)| {
+                assets::material(AssetKey::new(&path, AssetIndex::Material(Some(idx))), || {
+                    let PreparedMaterial {
+                        name,
+                        features,
+                        factors,
+                        texture,
+                    } = material;
+                    match texture {
+                        Some((path, image)) => assets::texture(AssetKey::new(&path, AssetIndex::Whole), || Ok(Texture::from_image(&image, Some(&name)))),
+                        None => Ok(assets::white()),
+                    }
+                    .map(|texture| MaterialPlugin::load(&name, texture, factors, features))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let fallback = assets::material(AssetKey::new(&path, AssetIndex::Material(None)), || {
+            Ok(MaterialPlugin::load(
+                "DEFAULT",
+                assets::white(),
+                base_color(DEFAULT_DIFFUSE, 1.),
+                MaterialFeatures::default(),
+            ))
+        })?;
+
+        objects
+            .into_iter()
+            .enumerate()
+            .map(|(idx, PreparedObject { levels, bounds, material })| {
+                let material = material
+                    .and_then(|id| materials.get(id))
+                    .unwrap_or(&fallback);
+                levels
+                    .into_iter()
+                    .enumerate()
+                    .map(|(level, (vertices, indices))| {
+                        assets::mesh(
+                            AssetKey::new(
+                                &path,
+                                AssetIndex::Mesh {
+                                    mesh: idx,
+                                    primitive: 0,
+                                    level,
+                                },
+                            ),
+                            || Ok(MeshPlugin::load_mesh(&vertices, &indices)),
+                        )
+                        .map(|mesh| Primitive {
+                            mesh,
+                            material: material.clone(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+                    .map(NonEmpty::from_vec)?
+                    .context("levels start out with the object itself")
+                    .map(|levels| (levels, bounds))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .pipe(NonEmpty::from_vec)
+            .context("file has no objects")
+            .map(|loaded| Model::from_levels(loaded, &GENERATED_LODS))
+    }
+}
+
+/// tobj resolves `mtllib` on its own without saying where it looked, so the statements are read again
+fn material_libraries(path: &Path) -> Vec<PathBuf> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    std::fs::read_to_string(path)
+        .map(|obj| {
+            obj.lines()
+                .filter_map(|line| line.trim().strip_prefix("mtllib "))
+                .map(|library| directory.join(library.trim()))
+                .collect_vec()
+        })
+        .unwrap_or_default()
+}
+
+/// decodes the diffuse texture, if there is one, it is drawn as it is rather than tinted by the diffuse color
+///
+/// a texture that can't be read or decoded is left out with a warning, like a missing `.mtl`
+fn prepare_material(directory: &Path, material: &tobj::Material) -> PreparedMaterial {
+    let alpha = material.dissolve.unwrap_or(1.);
+    let features = MaterialFeatures {
+        blend_mode: match alpha < 1. {
+            true => BlendMode::AlphaBlend,
+            false => BlendMode::Opaque,
+        },
+        ..Default::default()
+    };
+    material
+        .diffuse_texture
+        .as_ref()
+        .and_then(|texture| {
+            directory.join(texture).pipe(|path| {
+                std::fs::read(&path)
+                    .context("reading")
+                    .and_then(|data| image::load_from_memory(&data).context("bad image"))
+                    .tap_err(|e| {
+                        tracing::warn!(
+                            "no texture for material [{}], [{}] could not be loaded, drawing it untextured: {e:#}",
+                            material.name,
+                            path.display()
+                        )
+                    })
+                    .ok()
+                    .map(|image| (path, image))
+            })
+        })
+        .pipe(|texture| PreparedMaterial {
+            name: material.name.clone(),
+            features,
+            factors: match texture {
+                Some(_) => base_color([1., 1., 1.], alpha),
+                None => base_color(material.diffuse.unwrap_or(DEFAULT_DIFFUSE), alpha),
+            }
+            .tap_mut(|factors| {
+                // `Ks` is a color, the highlight is only scaled by its brightest channel
+                if let Some(specular) = material.specular {
+                    factors.specular.x = specular.into_iter().fold(0., f32::max);
+                }
+            }),
+            texture,
+        })
+}
+
+fn base_color([r, g, b]: [f32; 3], alpha: f32) -> MaterialFactors {
+    MaterialFactors {
+        base_color: Vec4::new(r, g, b, alpha),
+        ..Default::default()
+    }
+}
+
+/// normals missing from the file are averaged from the faces around every vertex
+fn vertices(mesh: &tobj::Mesh) -> Vec<ModelVertex> {
+    let positions = mesh
+        .positions
+        .chunks_exact(3)
+        .map(Vec3::from_slice)
+        .collect_vec();
+    let normals = match mesh.normals.len() == mesh.positions.len() {
+        true => mesh
+            .normals
+            .chunks_exact(3)
+            .map(Vec3::from_slice)
+            .collect_vec(),
+        false => generate_normals(&positions, &mesh.indices),
+    };
+    // obj counts v up from the bottom of the texture
+    let tex_coords = mesh
+        .texcoords
+        .chunks_exact(2)
+        .map(|uv| Vec2::new(uv[0], 1. - uv[1]))
+        .collect_vec();
+    positions
+        .iter()
+        .zip(normals)
+        .enumerate()
+        .map(|(idx, (position, normal))| ModelVertex {
+            position: position.extend(1.),
+            normal: normal.extend(1.),
+            tex_coords: tex_coords.get(idx).copied().unwrap_or_default(),
+            padding: pad(()),
+            joints: Vec4::ZERO,
+            weights: Vec4::ZERO,
+        })
+        .collect_vec()
+}
@@ -0,0 +1,146 @@
+use {
+    super::{
+        load_gltf::{import_file, GltfImport, Model},
+        load_obj::PreparedObj,
+    },
+    crate::run::rendering::scene::{Scene, SceneSelector},
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    nonempty::NonEmpty,
+    std::{
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicUsize, Ordering},
+    },
+};
+
+/// every format a model can come from, [loader_for] picks one by file extension
+const LOADERS: &[&dyn ModelLoader] = &[&GltfLoader, &ObjLoader];
+
+/// loading is split in two, so only the gpu upload has to happen where the renderer lives
+pub trait ModelLoader: Sync {
+    /// lowercase, without the dot
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// reading, decoding and mesh building, meant for a worker thread
+    fn prepare(&self, path: &Path, progress: &LoadProgress) -> Result<Box<dyn PreparedModels>>;
+}
+
+/// whatever a [ModelLoader] could do without the gpu
+pub trait PreparedModels: Send {
+    /// the file and everything it pulled in, a change to any of them means it has to be loaded again
+    fn sources(&self) -> Vec<PathBuf>;
+
+    fn load_models(self: Box<Self>) -> Result<NonEmpty<Model>>;
+
+    /// formats without scenes of their own get every model placed at the origin, picking any other than the first is warned about
+    fn load_scene(self: Box<Self>, scene: &SceneSelector) -> Result<Scene> {
+        if *scene != SceneSelector::Index(0) {
+            tracing::warn!("the map format has no scenes to pick from, ignoring {scene:?}");
+        }
+        self.load_models().map(Scene::from_models)
+    }
+}
+
+/// shared between the worker preparing a file and whatever shows how far along it is
+///
+/// the total grows while loading, as a file has to be read before it is known how much is in it
+#[derive(Debug, Default)]
+pub struct LoadProgress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl LoadProgress {
+    pub fn add_work(&self, steps: usize) {
+        self.total.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    pub fn finish_one(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// steps done and steps known about so far
+    pub fn steps(&self) -> (usize, usize) {
+        (self.done.load(Ordering::Relaxed), self.total.load(Ordering::Relaxed))
+    }
+
+    /// from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        match self.steps() {
+            (_, 0) => 0.,
+            (done, total) => done as f32 / total as f32,
+        }
+    }
+}
+
+pub fn loader_for(path: &Path) -> Result<&'static dyn ModelLoader> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase)
+        .with_context(|| format!("[{}] has no extension to pick a loader by", path.display()))?;
+    LOADERS
+        .iter()
+        .find(|loader| loader.extensions().contains(&extension.as_str()))
+        .copied()
+        .with_context(|| {
+            format!(
+                "no loader for [.{extension}] files, supported: [{}]",
+                LOADERS
+                    .iter()
+                    .flat_map(|loader| loader.extensions())
+                    .map(|extension| format!(".{extension}"))
+                    .join(", ")
+            )
+        })
+}
+
+pub struct GltfLoader;
+
+impl ModelLoader for GltfLoader {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["gltf", "glb"]
+    }
+
+    fn prepare(&self, path: &Path, progress: &LoadProgress) -> Result<Box<dyn PreparedModels>> {
+        import_file(path, progress).map(|gltf| Box::new(gltf) as _)
+    }
+}
+
+impl PreparedModels for GltfImport {
+    fn sources(&self) -> Vec<PathBuf> {
+        GltfImport::sources(self)
+    }
+
+    fn load_models(self: Box<Self>) -> Result<NonEmpty<Model>> {
+        Model::load_all(&self)?.context("file has no meshes")
+    }
+
+    fn load_scene(self: Box<Self>, scene: &SceneSelector) -> Result<Scene> {
+        scene
+            .find(&self.document)
+            .and_then(|scene| Scene::load(&self, scene))
+    }
+}
+
+pub struct ObjLoader;
+
+impl ModelLoader for ObjLoader {
+    fn extensions(&self) -> &'static [&'static str] {
+        &["obj"]
+    }
+
+    fn prepare(&self, path: &Path, progress: &LoadProgress) -> Result<Box<dyn PreparedModels>> {
+        PreparedObj::prepare(path, progress).map(|obj| Box::new(obj) as _)
+    }
+}
+
+impl PreparedModels for PreparedObj {
+    fn sources(&self) -> Vec<PathBuf> {
+        PreparedObj::sources(self)
+    }
+
+    fn load_models(self: Box<Self>) -> Result<NonEmpty<Model>> {
+        self.load().map(NonEmpty::new)
+    }
+}
@@ -0,0 +1,317 @@
+use {
+    super::{
+        load_gltf::{GltfImport, Model},
+        Primitive,
+    },
+    crate::run::rendering::camera::Camera,
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    nonempty::NonEmpty,
+    serde::Deserialize,
+    shader_types::{
+        camera::Z_NEAR,
+        glam::{IVec3, Vec4Swizzles},
+        model::ModelVertex,
+        padding::pad,
+        Instance,
+        Vec2,
+        Vec3,
+        Vec4,
+    },
+    std::{
+        cell::Cell,
+        collections::{HashMap, HashSet},
+    },
+    tap::prelude::*,
+};
+
+/// how far past a threshold the screen coverage has to go before the level switches, keeps models sitting right on one from flickering
+const HYSTERESIS: f32 = 0.15;
+/// grid cells along the longest side of a mesh for every generated level, paired with the screen coverage below which it is drawn
+pub const GENERATED_LODS: [(u32, f32); 2] = [(24, 0.25), (8, 0.08)];
+/// meshes smaller than this are cheap enough at any distance
+const MIN_SIMPLIFIED_TRIANGLES: usize = 256;
+/// a level has to drop at least this share of the triangles of the one before it to be worth a draw call of its own
+const MIN_REDUCTION: f32 = 0.25;
+
+/// a lower detail version of a model, drawn once it covers less than `coverage` of the screen height
+#[derive(Debug)]
+pub struct Lod {
+    pub primitives: NonEmpty<Primitive>,
+    pub coverage: f32,
+}
+
+/// the level picked for the previous frame, so a model only switches once it is clearly past a threshold
+#[derive(Debug, Default)]
+pub struct LodSelection(Cell<usize>);
+
+impl LodSelection {
+    /// 0 is full detail, `n` is `lods[n - 1]`
+    pub fn current(&self) -> usize {
+        self.0.get()
+    }
+
+    /// `threshold` gives the coverage below which each of the `levels` lower levels is drawn
+    fn select(&self, levels: usize, threshold: impl Fn(usize) -> f32, coverage: f32) -> usize {
+        let mut level = self.current().min(levels);
+        while level < levels && coverage < threshold(level) * (1. - HYSTERESIS) {
+            level += 1;
+        }
+        while level > 0 && coverage > threshold(level - 1) * (1. + HYSTERESIS) {
+            level -= 1;
+        }
+        level.tap(|level| self.0.set(*level))
+    }
+}
+
+/// `MSFT_lod` node extension, the nodes holding every lower level in order
+#[derive(Debug, Deserialize)]
+struct MsftLod {
+    ids: Vec<usize>,
+}
+
+/// the part of node extras `MSFT_lod` keeps its thresholds in, one per level starting with full detail
+#[derive(Debug, Deserialize)]
+struct LodExtras {
+    #[serde(rename = "MSFT_screencoverage", default)]
+    screen_coverage: Vec<f32>,
+}
+
+impl Model {
+    /// meshes get their lower levels generated, unless the node lists its own through `MSFT_lod` or the mesh is only ever drawn as one of those
+    pub fn load_for_node(context: &GltfImport, node: &gltf::Node<'_>, mesh: gltf::Mesh<'_>) -> Result<Option<Self>> {
+        load_msft_lods(context, node)
+            .context("loading MSFT_lod levels")?
+            .map(|lods| Self::load_levels(context, mesh.clone(), &[]).map(|model| model.map(|model| model.with_lods(lods))))
+            .unwrap_or_else(|| match msft_lod_meshes(&context.document).contains(&mesh.index()) {
+                true => Self::load_levels(context, mesh, &[]),
+                false => Self::load(context, mesh),
+            })
+    }
+
+    pub fn with_lods(self, lods: Vec<Lod>) -> Self {
+        Self { lods, ..self }
+    }
+
+    /// the level to draw `instance` with this frame, full detail without a camera
+    pub fn primitives_for(&self, camera: Option<&Camera>, instance: &Instance) -> &NonEmpty<Primitive> {
+        camera
+            .filter(|_| !self.lods.is_empty())
+            .map(|camera| {
+                let center = instance.position.xyz() + instance.rotation * (self.bounds.min + self.bounds.max) / 2.;
+                let radius = (self.bounds.max - self.bounds.min).length() / 2.;
+                // projected diameter over the height of the screen, both in ndc
+                let coverage = radius * camera.projection().y_axis.y / camera.position().distance(center).max(Z_NEAR);
+                self.lod_selection
+                    .select(self.lods.len(), |level| self.lods[level].coverage, coverage)
+            })
+            .and_then(|level| level.checked_sub(1))
+            .map(|level| &self.lods[level].primitives)
+            .unwrap_or(&self.primitives)
+    }
+}
+
+/// meshes that only nodes with `MSFT_lod` or the levels they list draw, they bring their own levels so generating more is wasted work
+pub fn msft_lod_meshes(document: &gltf::Document) -> HashSet<usize> {
+    let levels = document
+        .nodes()
+        .filter_map(|node| node.extension_value("MSFT_lod").cloned())
+        .filter_map(|extension| serde_json::from_value::<MsftLod>(extension).ok())
+        .flat_map(|MsftLod { ids }| ids)
+        .collect::<HashSet<_>>();
+    // the nodes holding the levels are ordinary nodes too, only the ones no `MSFT_lod` points at count as drawn on their own
+    let (lod, plain): (HashSet<_>, HashSet<_>) = document
+        .nodes()
+        .filter_map(|node| {
+            node.mesh()
+                .map(|mesh| (node.extension_value("MSFT_lod").is_some() || levels.contains(&node.index()), mesh.index()))
+        })
+        .partition_map(|(lod, mesh)| match lod {
+            true => itertools::Either::Left(mesh),
+            false => itertools::Either::Right(mesh),
+        });
+    lod.difference(&plain).copied().collect()
+}
+
+/// [None] for nodes without the extension, thresholds missing from the extras halve with every level
+///
+/// levels with nothing to draw as triangles are left out
+fn load_msft_lods(context: &GltfImport, node: &gltf::Node<'_>) -> Result<Option<Vec<Lod>>> {
+    node.extension_value("MSFT_lod")
+        .map(|extension| {
+            let MsftLod { ids } = serde_json::from_value(extension.clone()).context("parsing MSFT_lod")?;
+            let screen_coverage = node
+                .extras()
+                .as_ref()
+                .map(|extras| serde_json::from_str::<LodExtras>(extras.get()).context("parsing MSFT_screencoverage"))
+                .transpose()?
+                .map(|extras| extras.screen_coverage)
+                .unwrap_or_default();
+            ids.iter()
+                .enumerate()
+                .filter_map(|(idx, id)| {
+                    context
+                        .document
+                        .nodes()
+                        .nth(*id)
+                        .with_context(|| format!("no node at index [{id}]"))
+                        .and_then(|node| {
+                            node.mesh()
+                                .with_context(|| format!("node [{id}] has no mesh"))
+                        })
+                        .and_then(|mesh| Model::load_levels(context, mesh, &[]))
+                        .map(|model| {
+                            model.map(|model| Lod {
+                                primitives: model.primitives,
+                                coverage: screen_coverage
+                                    .get(idx)
+                                    .copied()
+                                    .unwrap_or_else(|| 0.5f32.powi(idx as i32 + 1)),
+                            })
+                        })
+                        .with_context(|| format!("loading level [{}]", idx + 1))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .transpose()
+}
+
+/// the mesh itself followed by one vertex clustered copy per entry of `cells`, stops at the first one not worth keeping
+pub fn simplified_levels(vertices: Vec<ModelVertex>, indices: Vec<u32>, cells: &[u32]) -> NonEmpty<(Vec<ModelVertex>, Vec<u32>)> {
+    let levels = NonEmpty::new((vertices, indices));
+    if levels.head.1.len() / 3 < MIN_SIMPLIFIED_TRIANGLES {
+        return levels;
+    }
+    cells
+        .iter()
+        .try_fold(levels, |levels, cells| {
+            let (vertices, indices) = &levels.head;
+            match simplify(vertices, indices, *cells)
+                .filter(|(_, simplified)| !simplified.is_empty() && simplified.len() as f32 <= levels.last().1.len() as f32 * (1. - MIN_REDUCTION))
+            {
+                Some(level) => Ok(levels.tap_mut(|levels| levels.push(level))),
+                None => Err(levels),
+            }
+        })
+        .unwrap_or_else(std::convert::identity)
+}
+
+/// merges every vertex with the others in its cell of a grid with `cells` cells along the longest side, triangles collapsing into a line or a point are dropped
+fn simplify(vertices: &[ModelVertex], indices: &[u32], cells: u32) -> Option<(Vec<ModelVertex>, Vec<u32>)> {
+    let (min, max) = vertices
+        .iter()
+        .fold((Vec3::MAX, Vec3::MIN), |(min, max), vertex| {
+            (min.min(vertex.position.xyz()), max.max(vertex.position.xyz()))
+        });
+    let cell_size = (max - min).max_element() / cells as f32;
+    if cell_size <= 0. || !cell_size.is_finite() {
+        return None;
+    }
+
+    // position, normal and tex coords summed up per cell, along with how many vertices went in
+    // joints and weights can't be averaged, the first vertex in a cell decides them
+    let mut cell_index = HashMap::<IVec3, u32>::new();
+    let mut sums = Vec::<(Vec3, Vec3, Vec2, f32, (Vec4, Vec4))>::new();
+    let remap = vertices
+        .iter()
+        .map(|vertex| {
+            let cell = ((vertex.position.xyz() - min) / cell_size)
+                .floor()
+                .as_ivec3();
+            let idx = *cell_index.entry(cell).or_insert_with(|| {
+                sums.push((Vec3::ZERO, Vec3::ZERO, Vec2::ZERO, 0., (vertex.joints, vertex.weights)));
+                sums.len() as u32 - 1
+            });
+            let (position, normal, tex_coords, count, _) = &mut sums[idx as usize];
+            *position += vertex.position.xyz();
+            *normal += vertex.normal.xyz();
+            *tex_coords += vertex.tex_coords;
+            *count += 1.;
+            idx
+        })
+        .collect_vec();
+
+    let indices = indices
+        .iter()
+        .map(|idx| remap.get(*idx as usize).copied())
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .tuples()
+        .filter(|(a, b, c)| a != b && b != c && a != c)
+        .flat_map(|(a, b, c)| [a, b, c])
+        .collect_vec();
+    let vertices = sums
+        .into_iter()
+        .map(|(position, normal, tex_coords, count, (joints, weights))| ModelVertex {
+            position: (position / count).extend(1.),
+            normal: normal.normalize_or_zero().extend(1.),
+            tex_coords: tex_coords / count,
+            padding: pad(()),
+            joints,
+            weights,
+        })
+        .collect_vec();
+    Some((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: [f32; 2] = [0.5, 0.1];
+
+    fn select(selection: &LodSelection, coverage: f32) -> usize {
+        selection.select(THRESHOLDS.len(), |level| THRESHOLDS[level], coverage)
+    }
+
+    #[test]
+    fn levels_follow_the_coverage() {
+        let selection = LodSelection::default();
+        assert_eq!(select(&selection, 1.), 0);
+        assert_eq!(select(&selection, 0.3), 1);
+        assert_eq!(select(&selection, 0.01), 2);
+        // jumps straight back up when it is clearly past every threshold
+        assert_eq!(select(&selection, 1.), 0);
+    }
+
+    #[test]
+    fn levels_only_switch_clearly_past_a_threshold() {
+        let selection = LodSelection::default();
+        // just below the threshold is not far enough to leave full detail
+        assert_eq!(select(&selection, 0.45), 0);
+        assert_eq!(select(&selection, 0.4), 1);
+        // and just above it is not far enough to come back
+        assert_eq!(select(&selection, 0.55), 1);
+        assert_eq!(select(&selection, 0.6), 0);
+    }
+
+    /// four meshes with a single triangle each, node 0 lists nodes 1 and 2 as its levels, node 3 draws mesh 2 as well and node 4 draws mesh 3
+    const MSFT_LOD: &str = r#"{
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["MSFT_lod"],
+        "buffers": [{ "byteLength": 36, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA" }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "accessors": [{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [0, 0, 0] }],
+        "meshes": [
+            { "primitives": [{ "attributes": { "POSITION": 0 } }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 } }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 } }] },
+            { "primitives": [{ "attributes": { "POSITION": 0 } }] }
+        ],
+        "nodes": [
+            { "mesh": 0, "extensions": { "MSFT_lod": { "ids": [1, 2] } } },
+            { "mesh": 1 },
+            { "mesh": 2 },
+            { "mesh": 2 },
+            { "mesh": 3 }
+        ],
+        "scenes": [{ "nodes": [0, 3, 4] }]
+    }"#;
+
+    #[test]
+    fn nodes_listed_by_msft_lod_keep_their_meshes_out_of_simplification() {
+        let gltf = gltf::Gltf::from_slice(MSFT_LOD.as_bytes()).expect("valid glTF");
+        assert_eq!(msft_lod_meshes(&gltf.document), HashSet::from([0, 1]));
+    }
+}
@@ -1,9 +1,16 @@
-use crate::{
-    bind_group_layout,
-    run::rendering::{
-        texture::Texture,
-        wgpu_ext::{bind_group::HasBindGroup, global_context::device},
+use {
+    crate::{
+        bind_group_layout,
+        run::rendering::{
+            identify::WithId,
+            pipeline::MaterialFeatures,
+            texture::Texture,
+            wgpu_ext::{bind_group::HasBindGroup, buffer::uniform::UniformBuffer, global_context::device},
+        },
     },
+    anyhow::Result,
+    shader_types::material::MaterialFactors,
+    std::cell::Cell,
 };
 
 bind_group_layout!(
@@ -28,6 +35,17 @@ bind_group_layout!(
                 ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                 count: None,
             },
+            // FACTORS
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
         ],
     }
 );
@@ -35,24 +53,33 @@ bind_group_layout!(
 pub struct MaterialPlugin;
 
 impl MaterialPlugin {
-    pub fn load(name: &str, texture: Texture) -> LoadedMaterial {
+    /// `factors` are multiplied with the texture, untextured materials go with [crate::run::rendering::assets::white]
+    pub fn load(name: &str, texture: WithId<Texture>, factors: MaterialFactors, features: MaterialFeatures) -> LoadedMaterial {
+        let factors_buffer = UniformBuffer::new_init(&factors);
         LoadedMaterial {
             name: name.into(),
+            features,
             bind_group: device().create_bind_group(&wgpu::BindGroupDescriptor {
                 label: struct_label!(),
                 layout: Self::bind_group_layout(),
                 entries: &[
                     wgpu::BindGroupEntry {
                         binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                        resource: wgpu::BindingResource::TextureView(&texture.as_ref().view),
                     },
                     wgpu::BindGroupEntry {
                         binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                        resource: wgpu::BindingResource::Sampler(&texture.as_ref().sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: factors_buffer.as_ref().as_entire_binding(),
                     },
                 ],
             }),
             texture,
+            factors: Cell::new(factors),
+            factors_buffer,
         }
     }
 }
@@ -60,7 +87,22 @@ impl MaterialPlugin {
 pub struct LoadedMaterial {
     #[allow(dead_code)]
     pub(crate) name: String,
+    pub(crate) features: MaterialFeatures,
+    /// shared with every other material using the same image
     #[allow(dead_code)]
-    pub(crate) texture: Texture,
+    pub(crate) texture: WithId<Texture>,
+    /// what `factors_buffer` holds, so they can be edited without reading them back
+    pub(crate) factors: Cell<MaterialFactors>,
+    factors_buffer: UniformBuffer<MaterialFactors>,
     pub(crate) bind_group: wgpu::BindGroup,
 }
+
+impl LoadedMaterial {
+    /// every primitive sharing the material picks up the change
+    pub async fn set_factors(&self, factors: MaterialFactors) -> Result<()> {
+        self.factors.set(factors);
+        self.factors_buffer
+            .write(0..1, move |buffer| buffer[0] = factors)
+            .await
+    }
+}
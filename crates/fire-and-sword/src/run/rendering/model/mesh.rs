@@ -7,7 +7,10 @@ use {
             global_context::device,
         },
     },
-    shader_types::model::ModelVertex,
+    anyhow::{Context, Result},
+    gltf::mesh::Mode,
+    itertools::Itertools,
+    shader_types::{model::ModelVertex, Vec3},
     tap::prelude::*,
     wgpu::{BindGroup, BindGroupLayout},
 };
@@ -15,15 +18,78 @@ use {
 #[derive(Debug, Clone, Copy)]
 pub struct MeshPlugin;
 
+/// averages the normals of the triangles around every vertex, weighted by their area
+///
+/// smooth wherever triangles share vertices, so meshes without any sharing come out flat
+pub fn generate_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    indices
+        .iter()
+        .map(|idx| *idx as usize)
+        .tuples()
+        .fold(vec![Vec3::ZERO; positions.len()], |normals, (a, b, c)| {
+            // not normalized, so bigger faces weigh more
+            let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+            normals.tap_mut(|normals| [a, b, c].into_iter().for_each(|idx| normals[idx] += normal))
+        })
+        .into_iter()
+        .map(Vec3::normalize_or_zero)
+        .collect_vec()
+}
+
+/// turns strips and fans into plain triangles, following the vertex order the glTF spec gives for them
+///
+/// [None] for points and lines, they have no triangles to draw
+pub fn triangle_list(name: &str, mode: Mode, indices: Vec<u32>) -> Option<Vec<u32>> {
+    let triangles = indices.len().saturating_sub(2);
+    match mode {
+        Mode::Triangles => indices.tap_mut(|indices| indices.truncate(indices.len() - indices.len() % 3)),
+        Mode::TriangleStrip => {
+            tracing::warn!("mesh [{name}] is a triangle strip, converting it to a list");
+            (0..triangles)
+                .flat_map(|i| match i % 2 {
+                    0 => [i, i + 1, i + 2],
+                    _ => [i, i + 2, i + 1],
+                })
+                .map(|i| indices[i])
+                .collect_vec()
+        }
+        Mode::TriangleFan => {
+            tracing::warn!("mesh [{name}] is a triangle fan, converting it to a list");
+            (0..triangles)
+                .flat_map(|i| [i + 1, i + 2, 0])
+                .map(|i| indices[i])
+                .collect_vec()
+        }
+        other => {
+            tracing::warn!("mesh [{name}] is drawn as [{other:?}], which can't be made into triangles, skipping it");
+            return None;
+        }
+    }
+    .pipe(Some)
+}
+
 pub struct LoadedMesh {
     #[allow(dead_code)]
     pub(crate) layout: &'static BindGroupLayout,
-    #[allow(dead_code)]
     pub(crate) vertex_buffer: StorageBuffer<ModelVertex>,
     pub(crate) index_buffer: IndexBuffer,
     pub(crate) bind_group: BindGroup,
 }
 
+impl LoadedMesh {
+    /// reads the indices back, an index past the vertices would have the vertex shader read out of bounds
+    pub async fn check_indices(&self) -> Result<()> {
+        let vertices = self.vertex_buffer.capacity();
+        self.index_buffer
+            .read(0..self.index_buffer.len() as u64)
+            .await?
+            .into_iter()
+            .find(|index| *index as usize >= vertices)
+            .map_or(Ok(()), |index| Err(anyhow::anyhow!("index [{index}] past [{vertices}] vertices")))
+            .with_context(|| format!("checking the [{}] indices on the gpu", self.index_buffer.len()))
+    }
+}
+
 bind_group_layout!(
     MeshPlugin,
     wgpu::BindGroupLayoutDescriptor {
@@ -66,3 +132,67 @@ impl MeshPlugin {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_drop_a_trailing_partial_triangle() {
+        assert_eq!(triangle_list("list", Mode::Triangles, vec![0, 1, 2, 3, 4]), Some(vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn strips_keep_every_triangle_facing_the_same_way() {
+        assert_eq!(
+            triangle_list("strip", Mode::TriangleStrip, vec![10, 11, 12, 13, 14]),
+            Some(vec![10, 11, 12, 11, 13, 12, 12, 13, 14])
+        );
+    }
+
+    #[test]
+    fn fans_go_around_the_first_vertex() {
+        assert_eq!(
+            triangle_list("fan", Mode::TriangleFan, vec![10, 11, 12, 13]),
+            Some(vec![11, 12, 10, 12, 13, 10])
+        );
+    }
+
+    #[test]
+    fn too_few_vertices_make_no_triangles() {
+        assert_eq!(triangle_list("strip", Mode::TriangleStrip, vec![0, 1]), Some(vec![]));
+        assert_eq!(triangle_list("fan", Mode::TriangleFan, vec![]), Some(vec![]));
+    }
+
+    #[test]
+    fn points_and_lines_are_skipped() {
+        [Mode::Points, Mode::Lines, Mode::LineLoop, Mode::LineStrip]
+            .into_iter()
+            .for_each(|mode| assert_eq!(triangle_list("lines", mode, vec![0, 1, 2]), None, "{mode:?}"));
+    }
+
+    #[test]
+    fn normals_face_away_from_counter_clockwise_triangles() {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        assert_eq!(generate_normals(&positions, &[0, 1, 2]), vec![Vec3::Z; 3]);
+        assert_eq!(generate_normals(&positions, &[0, 2, 1]), vec![-Vec3::Z; 3]);
+    }
+
+    #[test]
+    fn shared_vertices_average_their_faces_by_area() {
+        // a bigger triangle facing up and a smaller one facing +x, sharing the edge along z
+        let positions = [Vec3::ZERO, Vec3::Z, Vec3::new(2., 0., 0.), Vec3::Y];
+        let normals = generate_normals(&positions, &[0, 1, 2, 0, 3, 1]);
+        let expected = Vec3::new(1., 2., 0.).normalize();
+        [0, 1]
+            .into_iter()
+            .for_each(|shared| assert!(normals[shared].abs_diff_eq(expected, 1e-5), "{}", normals[shared]));
+        assert_eq!(normals[2], Vec3::Y);
+        assert_eq!(normals[3], Vec3::X);
+    }
+
+    #[test]
+    fn unused_vertices_get_no_normal() {
+        assert_eq!(generate_normals(&[Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::ONE], &[0, 1, 2])[3], Vec3::ZERO);
+    }
+}
@@ -0,0 +1,317 @@
+use {
+    super::{
+        camera::Camera,
+        pipeline::{BlendMode, EntryPoints, MaterialFeatures, PipelineCache, VertexLayout},
+        wgpu_ext::{
+            bind_group::{storage_entry, uniform_entry, HasBindGroup},
+            buffer::{storage::StorageBuffer, uniform::UniformBuffer},
+            compute::{workgroups, ComputeEncoderExt},
+            global_context::device,
+            profiler::GpuProfiler,
+        },
+    },
+    crate::bind_group_layout,
+    anyhow::{Context, Result},
+    futures::{FutureExt, StreamExt, TryStreamExt},
+    serde::Deserialize,
+    shader_types::{
+        glam::Affine3A,
+        particle::{Emitter, Particle, PARTICLE_WORKGROUP_SIZE},
+        Vec3,
+        Vec4,
+    },
+    std::{cell::Cell, collections::HashMap, sync::OnceLock, time::Instant},
+    tap::prelude::*,
+};
+
+/// fire, smoke and sparks, nodes pick one of these by name
+static PRESETS: &str = include_str!("../../../../../assets/particles/emitters.json");
+
+/// the presets are only parsed for the first emitter that asks for one
+fn presets() -> Result<&'static HashMap<String, EmitterParams>> {
+    static PARSED: OnceLock<serde_json::Result<HashMap<String, EmitterParams>>> = OnceLock::new();
+    PARSED
+        .get_or_init(|| serde_json::from_str(PRESETS))
+        .as_ref()
+        .map_err(|reason| anyhow::anyhow!("{reason}"))
+        .context("parsing emitter presets")
+}
+
+pub const SIMULATE_ENTRY_POINT: &str = "particles::particles_cs";
+/// a long stall shouldn't fling every particle across the map at once
+const MAX_TIME_STEP: f32 = 0.1;
+
+pub const PARTICLE_MATERIALS: [MaterialFeatures; 2] = [ParticleBlend::Additive.material(), ParticleBlend::Alpha.material()];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticleBlend {
+    /// glowing things, fire and sparks
+    Additive,
+    /// things that block light, smoke and dust
+    Alpha,
+}
+
+impl ParticleBlend {
+    pub const fn material(self) -> MaterialFeatures {
+        MaterialFeatures {
+            blend_mode: match self {
+                ParticleBlend::Additive => BlendMode::Additive,
+                ParticleBlend::Alpha => BlendMode::AlphaBlend,
+            },
+            double_sided: true,
+            entry_points: EntryPoints {
+                vertex: "particles::particle_vs",
+                fragment: "particles::particle_fs",
+            },
+            vertex_layout: VertexLayout::Particles,
+        }
+    }
+}
+
+/// what an emitter looks like, read from `assets/particles/emitters.json` or straight from node extras
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmitterParams {
+    pub blend: ParticleBlend,
+    /// fixed once the emitter is created
+    pub max_particles: u32,
+    /// per second
+    pub rate: f32,
+    /// in seconds
+    pub lifetime: f32,
+    /// how far from the node particles spawn
+    #[serde(default)]
+    pub radius: f32,
+    pub velocity: [f32; 3],
+    /// random velocity added in every direction
+    #[serde(default)]
+    pub spread: f32,
+    #[serde(default)]
+    pub acceleration: [f32; 3],
+    /// at birth and at death
+    pub size: [f32; 2],
+    /// at birth and at death
+    pub color: [[f32; 4]; 2],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum EmitterSource {
+    Preset(String),
+    Inline(EmitterParams),
+}
+
+/// the part of node extras this module cares about, e.g. `{ "emitter": "fire" }`
+///
+/// only the key is looked for here, extras exported for anything else are none of our business
+#[derive(Debug, Deserialize)]
+struct NodeExtras {
+    emitter: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct EmitterClock {
+    last_update: Option<Instant>,
+    /// particles owed from earlier steps, spawned once there is a whole one
+    pending: f32,
+    cursor: u32,
+    step: u32,
+}
+
+/// particles simulated and drawn straight out of a storage buffer, attached to a scene node
+pub struct ParticleEmitter {
+    pub params: EmitterParams,
+    emitter: UniformBuffer<Emitter>,
+    simulate_bind_group: wgpu::BindGroup,
+    pub(crate) draw_bind_group: wgpu::BindGroup,
+    clock: Cell<EmitterClock>,
+    // keeps the particles alive for both bind groups
+    _particles: StorageBuffer<Particle>,
+}
+
+impl std::fmt::Debug for ParticleEmitter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParticleEmitter")
+            .field("params", &self.params)
+            .finish_non_exhaustive()
+    }
+}
+
+bind_group_layout!(
+    ParticleEmitter,
+    wgpu::BindGroupLayoutDescriptor {
+        label: struct_label!(),
+        entries: &[
+            // PARTICLES
+            storage_entry(0, wgpu::ShaderStages::VERTEX, true),
+            // EMITTER
+            uniform_entry(1, wgpu::ShaderStages::VERTEX),
+        ],
+    }
+);
+
+bind_group_layout!(
+    ParticleSystem,
+    SIMULATION_BIND_GROUP,
+    wgpu::BindGroupLayoutDescriptor {
+        label: struct_label!(),
+        entries: &[
+            // PARTICLES
+            storage_entry(0, wgpu::ShaderStages::COMPUTE, false),
+            // EMITTER
+            uniform_entry(1, wgpu::ShaderStages::COMPUTE),
+        ],
+    }
+);
+
+impl ParticleEmitter {
+    pub fn new(params: EmitterParams) -> Self {
+        let particles = StorageBuffer::<Particle>::new_empty(params.max_particles.max(1) as _);
+        let emitter = UniformBuffer::new_init(&Emitter::default());
+        let bind_group = |layout| {
+            device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: struct_label!(),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: particles.as_ref().as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: emitter.as_ref().as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        Self {
+            params,
+            simulate_bind_group: bind_group(ParticleSystem::bind_group_layout()),
+            draw_bind_group: bind_group(Self::bind_group_layout()),
+            emitter,
+            clock: Default::default(),
+            _particles: particles,
+        }
+    }
+
+    /// [None] for nodes without an `emitter` in their extras, emitters that don't parse are left out with a warning
+    pub fn from_extras(node: &str, extras: &gltf::json::Extras) -> Option<Self> {
+        extras
+            .as_ref()
+            .and_then(|extras| serde_json::from_str::<NodeExtras>(extras.get()).ok())
+            .and_then(|extras| extras.emitter)
+            .and_then(|emitter| {
+                serde_json::from_value::<EmitterSource>(emitter)
+                    .context("parsing emitter")
+                    .and_then(|source| match source {
+                        EmitterSource::Inline(params) => Ok(params),
+                        EmitterSource::Preset(name) => presets()?
+                            .get(&name)
+                            .copied()
+                            .with_context(|| format!("no emitter preset named [{name}]")),
+                    })
+                    .tap_err(|e| tracing::warn!("node [{node}] has an emitter that can't be loaded, leaving it out: {e:#}"))
+                    .ok()
+            })
+            .map(Self::new)
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.params.max_particles.max(1)
+    }
+
+    /// advances the clock and uploads what the next simulation step and draw need
+    async fn update(&self, transform: Affine3A, camera: &Camera) -> Result<()> {
+        let EmitterParams {
+            rate,
+            lifetime,
+            radius,
+            velocity,
+            spread,
+            acceleration,
+            size: [size_start, size_end],
+            color: [color_start, color_end],
+            ..
+        } = self.params;
+        let capacity = self.capacity();
+        let now = Instant::now();
+        let clock = self.clock.get();
+        let delta_time = clock
+            .last_update
+            .map(|last_update| (now - last_update).as_secs_f32().min(MAX_TIME_STEP))
+            .unwrap_or_default();
+        let pending = clock.pending + rate * delta_time;
+        let spawn_count = (pending.floor() as u32).min(capacity);
+        self.clock.set(EmitterClock {
+            last_update: Some(now),
+            pending: pending - pending.floor(),
+            cursor: (clock.cursor + spawn_count) % capacity,
+            step: clock.step.wrapping_add(1),
+        });
+
+        let billboard_right = camera.look().cross(Vec3::Y).normalize();
+        let emitter = Emitter {
+            origin: transform.transform_point3(Vec3::ZERO).extend(radius),
+            velocity: transform
+                .transform_vector3(Vec3::from(velocity))
+                .extend(spread),
+            acceleration: Vec3::from(acceleration).extend(delta_time),
+            color_start: Vec4::from_array(color_start),
+            color_end: Vec4::from_array(color_end),
+            billboard_right: billboard_right.extend(0.),
+            billboard_up: billboard_right.cross(camera.look()).extend(0.),
+            size_start,
+            size_end,
+            lifetime,
+            padding: 0.,
+            spawn_start: clock.cursor,
+            spawn_count,
+            seed: clock.step,
+            capacity,
+        };
+        self.emitter
+            .write(0..1u64, move |buf| buf[0] = emitter)
+            .await
+            .context("writing emitter")
+    }
+}
+
+/// steps every emitter in the scene forward on the gpu
+pub struct ParticleSystem;
+
+impl ParticleSystem {
+    /// uploads the next step of every emitter, `emitters` are paired with the world transform of the node holding them
+    pub async fn update(emitters: &[(Affine3A, &ParticleEmitter)], camera: &Camera) -> Result<()> {
+        emitters
+            .iter()
+            .enumerate()
+            .pipe(futures::stream::iter)
+            .map(Ok)
+            .try_for_each(|(idx, (transform, emitter))| {
+                emitter
+                    .update(*transform, camera)
+                    .map(move |result| result.with_context(|| format!("updating emitter [{idx}]")))
+            })
+            .await
+    }
+
+    /// records the simulation into the frame, ahead of the render pass drawing the particles
+    pub fn dispatch(
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &mut PipelineCache,
+        profiler: Option<&mut GpuProfiler>,
+        emitters: &[(Affine3A, &ParticleEmitter)],
+    ) {
+        if emitters.is_empty() {
+            return;
+        }
+        let timestamp_writes = profiler.and_then(|profiler| profiler.compute_pass_writes("particles"));
+        encoder.compute_pass("particles", pipelines.get_or_create_compute(SIMULATE_ENTRY_POINT), timestamp_writes, |pass| {
+            emitters.iter().for_each(|(_, emitter)| {
+                pass.set_bind_group(0, &emitter.simulate_bind_group, &[]);
+                pass.dispatch_workgroups(workgroups(emitter.capacity(), PARTICLE_WORKGROUP_SIZE), 1, 1);
+            })
+        })
+    }
+}
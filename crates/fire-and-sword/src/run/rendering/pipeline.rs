@@ -0,0 +1,395 @@
+use {
+    super::{
+        camera::CameraPlugin,
+        deferred::{GBuffer, LIGHT_VOLUME_MATERIAL},
+        instance::InstancePlugin,
+        light_source::{LightClusters, LightSourcePlugin, CLUSTER_ENTRY_POINT},
+        model::{material::MaterialPlugin, mesh::MeshPlugin},
+        particles::{ParticleEmitter, ParticleSystem, PARTICLE_MATERIALS, SIMULATE_ENTRY_POINT},
+        render_pass::{gizmo::GIZMO_MATERIAL, text::TEXT_MATERIAL},
+        skin::JointMatrices,
+        text::GlyphAtlas,
+        texture,
+        wgpu_ext::{bind_group::BindGroupSet, global_context::device},
+    },
+    anyhow::{Context, Result},
+    shader_types::{gizmo::GizmoVertex, text::GlyphInstance},
+    std::collections::HashMap,
+    tracing::{debug, info},
+};
+
+/// every set of the pipeline layout, indexed by the `descriptor_set` used in `crates/shaders`
+pub fn bind_group_sets() -> [BindGroupSet; 6] {
+    [
+        // 0
+        BindGroupSet::of::<CameraPlugin>(),
+        // 1
+        BindGroupSet::of::<MeshPlugin>(),
+        // 2
+        BindGroupSet::of::<MaterialPlugin>(),
+        // 3
+        BindGroupSet::of::<InstancePlugin>(),
+        // 4
+        BindGroupSet::of::<LightSourcePlugin>(),
+        // 5
+        BindGroupSet::of::<JointMatrices>(),
+    ]
+}
+
+/// the sets of the pipeline `entry_point` runs in, anything that isn't an overlay or compute shader goes through the mesh layout
+pub fn entry_point_sets(entry_point: &str) -> Vec<BindGroupSet> {
+    match entry_point {
+        SIMULATE_ENTRY_POINT => vec![BindGroupSet::of::<ParticleSystem>()],
+        CLUSTER_ENTRY_POINT => vec![BindGroupSet::of::<LightClusters>()],
+        _ => [GIZMO_MATERIAL, TEXT_MATERIAL, LIGHT_VOLUME_MATERIAL]
+            .into_iter()
+            .chain(PARTICLE_MATERIALS)
+            .find(|MaterialFeatures { entry_points, .. }| entry_points.vertex == entry_point || entry_points.fragment == entry_point)
+            .map(|material| material.vertex_layout.bind_group_sets())
+            .unwrap_or_else(|| bind_group_sets().into()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum BlendMode {
+    #[default]
+    Opaque,
+    AlphaBlend,
+    /// adds up to brighter colors wherever surfaces overlap, order doesn't matter
+    Additive,
+}
+
+const ADDITIVE_BLENDING: wgpu::BlendState = wgpu::BlendState {
+    color: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::SrcAlpha,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+    alpha: wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    },
+};
+
+impl BlendMode {
+    fn blend_state(self) -> wgpu::BlendState {
+        match self {
+            BlendMode::Opaque => wgpu::BlendState::REPLACE,
+            BlendMode::AlphaBlend => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => ADDITIVE_BLENDING,
+        }
+    }
+    /// blended surfaces are tested against depth but don't occlude what's behind them
+    fn writes_depth(self) -> bool {
+        matches!(self, BlendMode::Opaque)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntryPoints {
+    pub vertex: &'static str,
+    pub fragment: &'static str,
+}
+
+impl Default for EntryPoints {
+    fn default() -> Self {
+        Self {
+            vertex: "main_vs",
+            fragment: "main_fs",
+        }
+    }
+}
+
+/// where the vertex shader gets its vertices from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum VertexLayout {
+    /// pulled from [MeshPlugin] storage by `vertex_index`, no vertex buffers
+    #[default]
+    Pulled,
+    /// [GizmoVertex] attributes in the first vertex buffer
+    Gizmo,
+    /// one [GlyphInstance] per instance, the quad comes from `vertex_index`
+    Text,
+    /// one particle per instance, pulled from the storage of a [ParticleEmitter]
+    Particles,
+    /// one light per instance, the cube around it comes from `vertex_index`
+    LightVolume,
+}
+
+impl VertexLayout {
+    /// overlays only need the camera, so they don't have to wait for a mesh or material to be bound
+    fn bind_group_sets(self) -> Vec<BindGroupSet> {
+        match self {
+            VertexLayout::Pulled => bind_group_sets().into(),
+            VertexLayout::Gizmo => vec![BindGroupSet::of::<CameraPlugin>()],
+            VertexLayout::Text => vec![BindGroupSet::of::<CameraPlugin>(), BindGroupSet::of::<GlyphAtlas>()],
+            VertexLayout::Particles => vec![BindGroupSet::of::<CameraPlugin>(), BindGroupSet::of::<ParticleEmitter>()],
+            VertexLayout::LightVolume => vec![
+                BindGroupSet::of::<CameraPlugin>(),
+                BindGroupSet::of::<GBuffer>(),
+                BindGroupSet::of::<LightSourcePlugin>(),
+            ],
+        }
+    }
+
+    const GIZMO_ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4];
+    const TEXT_ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32x4];
+
+    fn buffers(self) -> &'static [wgpu::VertexBufferLayout<'static>] {
+        match self {
+            VertexLayout::Pulled | VertexLayout::Particles | VertexLayout::LightVolume => &[],
+            VertexLayout::Gizmo => &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<GizmoVertex>() as _,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &Self::GIZMO_ATTRIBUTES,
+            }],
+            VertexLayout::Text => &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<GlyphInstance>() as _,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &Self::TEXT_ATTRIBUTES,
+            }],
+        }
+    }
+}
+
+/// the part of the pipeline that is decided by the material being drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct MaterialFeatures {
+    pub blend_mode: BlendMode,
+    pub double_sided: bool,
+    pub entry_points: EntryPoints,
+    pub vertex_layout: VertexLayout,
+}
+
+/// what the fragment shader writes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorTargets {
+    /// just the surface, blended the way the material wants
+    #[default]
+    Surface,
+    /// the surface followed by every [GBuffer] texture, nothing is blended
+    GBuffer,
+}
+
+impl ColorTargets {
+    fn targets(self, color_format: wgpu::TextureFormat, blend_mode: BlendMode) -> Vec<Option<wgpu::ColorTargetState>> {
+        let target = |format, blend| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        };
+        match self {
+            ColorTargets::Surface => vec![target(color_format, Some(blend_mode.blend_state()))],
+            ColorTargets::GBuffer => std::iter::once(target(color_format, Some(wgpu::BlendState::REPLACE)))
+                .chain(GBuffer::FORMATS.map(|format| target(format, None)))
+                .collect(),
+        }
+    }
+}
+
+/// the part of the pipeline that is decided by the pass it is drawn in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PassFeatures {
+    pub color_format: wgpu::TextureFormat,
+    pub color_targets: ColorTargets,
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub topology: wgpu::PrimitiveTopology,
+    pub polygon_mode: wgpu::PolygonMode,
+    pub sample_count: u32,
+}
+
+impl PassFeatures {
+    pub fn new(color_format: wgpu::TextureFormat) -> Self {
+        Self {
+            color_format,
+            color_targets: ColorTargets::Surface,
+            depth_format: Some(texture::Texture::DEPTH_FORMAT),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            sample_count: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineFeatures {
+    pub material: MaterialFeatures,
+    pub pass: PassFeatures,
+}
+
+/// builds render pipelines on demand, one per distinct [PipelineFeatures], and compute pipelines, one per entry point
+pub struct PipelineCache {
+    shader: wgpu::ShaderModule,
+    layouts: HashMap<VertexLayout, wgpu::PipelineLayout>,
+    pipelines: HashMap<PipelineFeatures, wgpu::RenderPipeline>,
+    compute_pipelines: HashMap<&'static str, wgpu::ComputePipeline>,
+}
+
+impl PipelineCache {
+    pub fn new(shader: wgpu::ShaderModule) -> Self {
+        Self {
+            shader,
+            layouts: [
+                VertexLayout::Pulled,
+                VertexLayout::Gizmo,
+                VertexLayout::Text,
+                VertexLayout::Particles,
+                VertexLayout::LightVolume,
+            ]
+            .into_iter()
+            .map(|vertex_layout| {
+                (
+                    vertex_layout,
+                    device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                        label: label!(format!("{vertex_layout:?} Pipeline Layout")),
+                        bind_group_layouts: &vertex_layout
+                            .bind_group_sets()
+                            .into_iter()
+                            .map(|set| set.layout)
+                            .collect::<Vec<_>>(),
+                        push_constant_ranges: &[],
+                    }),
+                )
+            })
+            .collect(),
+            pipelines: Default::default(),
+            compute_pipelines: Default::default(),
+        }
+    }
+
+    pub fn get_or_create(&mut self, features: PipelineFeatures) -> &wgpu::RenderPipeline {
+        let Self {
+            shader, layouts, pipelines, ..
+        } = self;
+        pipelines
+            .entry(features)
+            .or_insert_with(|| Self::create(shader, layouts, features))
+    }
+
+    /// for a `#[spirv(compute(threads(..)))]` entry point, laid out with [entry_point_sets]
+    pub fn get_or_create_compute(&mut self, entry_point: &'static str) -> &wgpu::ComputePipeline {
+        let Self { shader, compute_pipelines, .. } = self;
+        compute_pipelines
+            .entry(entry_point)
+            .or_insert_with(|| Self::create_compute(shader, entry_point))
+    }
+
+    fn create_compute(shader: &wgpu::ShaderModule, entry_point: &'static str) -> wgpu::ComputePipeline {
+        debug!("building compute pipeline for [{entry_point}]");
+        device().create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: label!(entry_point),
+            layout: Some(
+                &device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: label!(format!("{entry_point} Pipeline Layout")),
+                    bind_group_layouts: &entry_point_sets(entry_point)
+                        .into_iter()
+                        .map(|set| set.layout)
+                        .collect::<Vec<_>>(),
+                    push_constant_ranges: &[],
+                }),
+            ),
+            module: shader,
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        })
+    }
+
+    fn create(
+        shader: &wgpu::ShaderModule,
+        layouts: &HashMap<VertexLayout, wgpu::PipelineLayout>,
+        features @ PipelineFeatures {
+            material:
+                MaterialFeatures {
+                    blend_mode,
+                    double_sided,
+                    entry_points: EntryPoints { vertex, fragment },
+                    vertex_layout,
+                },
+            pass:
+                PassFeatures {
+                    color_format,
+                    color_targets,
+                    depth_format,
+                    topology,
+                    polygon_mode,
+                    sample_count,
+                },
+        }: PipelineFeatures,
+    ) -> wgpu::RenderPipeline {
+        debug!("building render pipeline for {features:#?}");
+        device().create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: label!(format!("{features:?}")),
+            layout: layouts.get(&vertex_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some(vertex),
+                buffers: vertex_layout.buffers(),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some(fragment),
+                targets: &color_targets.targets(color_format, blend_mode),
+
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // WARN: huge perf hit when disabled, only double sided materials opt out
+                cull_mode: (!double_sided).then_some(wgpu::Face::Back),
+                polygon_mode,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: depth_format.map(|format| wgpu::DepthStencilState {
+                format,
+                depth_write_enabled: blend_mode.writes_depth(),
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    /// swaps in a new shader module, rebuilding every pipeline that was already in use
+    ///
+    /// if any of them fails validation the cache is left untouched
+    pub async fn reload(&mut self, shader: wgpu::ShaderModule) -> Result<()> {
+        device().push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipelines = self
+            .pipelines
+            .keys()
+            .map(|features| (*features, Self::create(&shader, &self.layouts, *features)))
+            .collect::<HashMap<_, _>>();
+        let compute_pipelines = self
+            .compute_pipelines
+            .keys()
+            .map(|entry_point| (*entry_point, Self::create_compute(&shader, entry_point)))
+            .collect::<HashMap<_, _>>();
+        let rebuilt = pipelines.len() + compute_pipelines.len();
+        device()
+            .pop_error_scope()
+            .await
+            .map_or(Ok(()), |error| Err(anyhow::anyhow!("{error}")))
+            .with_context(|| format!("rebuilding [{rebuilt}] pipelines"))
+            .map(|_| {
+                info!("rebuilt [{rebuilt}] pipelines");
+                self.shader = shader;
+                self.pipelines = pipelines;
+                self.compute_pipelines = compute_pipelines;
+            })
+    }
+}
@@ -0,0 +1,224 @@
+use {
+    super::wgpu_ext::bind_group::BindGroupSet,
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    std::collections::{BTreeMap, BTreeSet},
+    tap::prelude::*,
+    tracing::{debug, warn},
+    wgpu::naga::{self, AddressSpace, Module, StorageAccess, TypeInner},
+};
+
+/// what a shader expects to find at a given set and binding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReflectedBinding {
+    Uniform,
+    Storage { read_only: bool },
+    Texture { dimension: naga::ImageDimension },
+    Sampler { comparison: bool },
+    Other,
+}
+
+/// an entry point and every (set, binding) in its interface
+#[derive(Debug, Clone)]
+pub struct EntryPoint {
+    pub name: String,
+    pub stage: wgpu::ShaderStages,
+    pub bindings: BTreeSet<(u32, u32)>,
+}
+
+pub fn reflect(spirv: &[u8]) -> Result<Module> {
+    naga::front::spv::parse_u8_slice(spirv, &Default::default()).context("parsing SPIR-V")
+}
+
+const OP_ENTRY_POINT: u32 = 15;
+const OP_DECORATE: u32 = 71;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+
+fn execution_model_stage(model: u32) -> wgpu::ShaderStages {
+    match model {
+        0 => wgpu::ShaderStages::VERTEX,
+        4 => wgpu::ShaderStages::FRAGMENT,
+        5 => wgpu::ShaderStages::COMPUTE,
+        _ => wgpu::ShaderStages::NONE,
+    }
+}
+
+/// naga doesn't keep track of which globals an entry point touches, but since SPIR-V 1.4
+/// every `OpEntryPoint` lists all of them in its interface, so they are read straight from the binary
+fn entry_points(spirv: &[u32]) -> Option<Vec<EntryPoint>> {
+    let version = spirv
+        .get(1)
+        .map(|version| ((version >> 16) & 0xff, (version >> 8) & 0xff))?;
+    if version < (1, 4) {
+        return None;
+    }
+    // every instruction starts with a word holding its length in the high and the opcode in the low half
+    let instructions = std::iter::successors(spirv.get(5..), |rest| rest.get((rest.first()? >> 16).max(1) as usize..))
+        .filter_map(|rest| rest.get(..(rest.first()? >> 16) as usize))
+        .collect_vec();
+    let mut sets = BTreeMap::<u32, u32>::new();
+    let mut bindings = BTreeMap::<u32, u32>::new();
+    instructions
+        .iter()
+        .filter(|instruction| instruction[0] & 0xffff == OP_DECORATE)
+        .for_each(|instruction| match instruction[1..] {
+            [target, DECORATION_DESCRIPTOR_SET, set] => drop(sets.insert(target, set)),
+            [target, DECORATION_BINDING, binding] => drop(bindings.insert(target, binding)),
+            _ => {}
+        });
+    instructions
+        .iter()
+        .filter(|instruction| instruction[0] & 0xffff == OP_ENTRY_POINT)
+        .filter_map(|instruction| {
+            // [opcode, execution model, function id, name..., interface ids...]
+            let name_words = instruction
+                .get(3..)?
+                .iter()
+                .position(|word| word.to_le_bytes().contains(&0))?
+                + 1;
+            let name = instruction[3..3 + name_words]
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .take_while(|byte| *byte != 0)
+                .collect_vec()
+                .pipe(String::from_utf8)
+                .ok()?;
+            Some(EntryPoint {
+                name,
+                stage: execution_model_stage(instruction[1]),
+                bindings: instruction
+                    .get(3 + name_words..)?
+                    .iter()
+                    .filter_map(|id| Some((*sets.get(id)?, *bindings.get(id)?)))
+                    .collect(),
+            })
+        })
+        .collect_vec()
+        .pipe(Some)
+}
+
+fn binding_of(module: &Module, global: &naga::GlobalVariable) -> ReflectedBinding {
+    match (global.space, &module.types[global.ty].inner) {
+        (AddressSpace::Uniform, _) => ReflectedBinding::Uniform,
+        (AddressSpace::Storage { access }, _) => ReflectedBinding::Storage {
+            read_only: !access.contains(StorageAccess::STORE),
+        },
+        (AddressSpace::Handle, TypeInner::Image { dim, .. }) => ReflectedBinding::Texture { dimension: *dim },
+        (AddressSpace::Handle, TypeInner::Sampler { comparison }) => ReflectedBinding::Sampler { comparison: *comparison },
+        _ => ReflectedBinding::Other,
+    }
+}
+
+/// every resource binding declared by the module, keyed by (set, binding)
+///
+/// entry points built with different pipeline layouts can declare different resources under the same key
+pub fn reflected_bindings(module: &Module) -> BTreeMap<(u32, u32), Vec<ReflectedBinding>> {
+    module
+        .global_variables
+        .iter()
+        .filter_map(|(_, variable)| {
+            variable
+                .binding
+                .as_ref()
+                .map(|binding| ((binding.group, binding.binding), binding_of(module, variable)))
+        })
+        .into_group_map()
+        .into_iter()
+        .collect()
+}
+
+fn matches(reflected: ReflectedBinding, expected: &wgpu::BindingType) -> bool {
+    match (reflected, expected) {
+        (
+            ReflectedBinding::Uniform,
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                ..
+            },
+        ) => true,
+        // a read-write binding can always back a read-only one, the other way around fails
+        (
+            ReflectedBinding::Storage { read_only },
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: layout_read_only },
+                ..
+            },
+        ) => read_only || !layout_read_only,
+        (ReflectedBinding::Texture { dimension }, wgpu::BindingType::Texture { view_dimension, .. }) => matches!(
+            (dimension, view_dimension),
+            (naga::ImageDimension::D1, wgpu::TextureViewDimension::D1)
+                | (naga::ImageDimension::D2, wgpu::TextureViewDimension::D2 | wgpu::TextureViewDimension::D2Array)
+                | (naga::ImageDimension::D3, wgpu::TextureViewDimension::D3)
+                | (
+                    naga::ImageDimension::Cube,
+                    wgpu::TextureViewDimension::Cube | wgpu::TextureViewDimension::CubeArray
+                )
+        ),
+        (ReflectedBinding::Sampler { comparison }, wgpu::BindingType::Sampler(sampler)) => {
+            comparison == matches!(sampler, wgpu::SamplerBindingType::Comparison)
+        }
+        (ReflectedBinding::Other, _) => true,
+        _ => false,
+    }
+}
+
+/// checks every binding each entry point uses against the bind group layouts its pipeline is built with
+///
+/// naga merges globals from all entry points, so a binding passes when any declaration under its key fits the layout.
+/// all mismatches are reported at once
+pub fn validate(module: &Module, spirv: &[u8], sets_for: impl Fn(&str) -> Vec<BindGroupSet>) -> Result<()> {
+    let Some(entry_points) = entry_points(&wgpu::util::make_spirv_raw(spirv)) else {
+        warn!("SPIR-V older than 1.4 doesn't list resources used by entry points, skipping layout validation");
+        return Ok(());
+    };
+    let reflected = reflected_bindings(module).tap(|bindings| debug!("reflected bindings: {bindings:#?}"));
+    entry_points
+        .iter()
+        .flat_map(
+            |EntryPoint {
+                 name: entry_point,
+                 stage,
+                 bindings,
+             }| {
+                let sets = sets_for(entry_point);
+                bindings
+                    .iter()
+                    .filter_map(|&(set, binding)| {
+                        let declared = reflected
+                            .get(&(set, binding))
+                            .map(Vec::as_slice)
+                            .unwrap_or_default();
+                        let describe = || format!("[{entry_point}] set [{set}] binding [{binding}] ({declared:?} used in {stage:?})");
+                        let Some(BindGroupSet { name, entries, .. }) = sets.get(set as usize) else {
+                            return Some(format!("{}: pipeline layout only has [{}] sets", describe(), sets.len()));
+                        };
+                        let Some(entry) = entries.iter().find(|entry| entry.binding == binding) else {
+                            return Some(format!("{}: [{name}] has no such binding", describe()));
+                        };
+                        if !declared
+                            .iter()
+                            .any(|reflected| matches(*reflected, &entry.ty))
+                        {
+                            return Some(format!("{}: [{name}] declares it as {:?}", describe(), entry.ty));
+                        }
+                        (!entry.visibility.contains(*stage)).then(|| format!("{}: [{name}] only makes it visible to {:?}", describe(), entry.visibility))
+                    })
+                    .collect_vec()
+            },
+        )
+        .collect_vec()
+        .pipe(|errors| match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow::anyhow!("{}", errors.join("\n"))),
+        })
+        .context("shader bindings don't match the pipeline layout")
+}
+
+/// SPIR-V naga can't parse is only warned about, a passthrough adapter may still accept it
+pub fn validate_spirv(spirv: &[u8], sets_for: impl Fn(&str) -> Vec<BindGroupSet>) -> Result<()> {
+    match reflect(spirv) {
+        Ok(module) => validate(&module, spirv, sets_for),
+        Err(reason) => Ok(warn!("could not reflect shaders, skipping layout validation:\n{reason:?}")),
+    }
+}
@@ -1,15 +1,37 @@
 use {
     super::{
         camera::{Camera, CameraPlugin},
+        debug_view::DebugView,
+        deferred::{GBuffer, G_BUFFER_ENTRY_POINT},
+        identify::WithId,
         model::{Primitive, RenderPassDrawModelExt},
-        wgpu_ext::{bind_group::HasBindGroup, buffer::storage::StorageBuffer, global_context::device},
+        pipeline::{BlendMode, ColorTargets, EntryPoints, MaterialFeatures, PassFeatures, PipelineCache, PipelineFeatures},
+        skin::JointMatrices,
+        text::GlyphAtlas,
+        wgpu_ext::{
+            bind_group::HasBindGroup,
+            buffer::{storage::StorageBuffer, vertex::VertexBuffer},
+            global_context::device,
+        },
     },
     crate::bind_group_layout,
     anyhow::{Context, Result},
     futures::{FutureExt, StreamExt, TryStreamExt},
-    shader_types::Instance,
-    std::{collections::BTreeMap, ops::Range},
+    gizmo::{GIZMO_MATERIAL, MAX_GIZMO_VERTICES},
+    itertools::Itertools,
+    particles::QueuedParticles,
+    shader_types::{
+        bytemuck::{AnyBitPattern, NoUninit},
+        gizmo::GizmoVertex,
+        text::GlyphInstance,
+        Instance,
+        Vec2,
+    },
+    std::{any::type_name, collections::BTreeMap, ops::Range},
     tap::prelude::*,
+    text::{MAX_GLYPHS, TEXT_MATERIAL},
+    tracing::warn,
+    wgpu::WasmNotSend,
 };
 
 bind_group_layout!(
@@ -75,6 +97,45 @@ impl InstanceSyncBuffer {
     }
 }
 
+/// vertices queued during a frame, drawn straight out of a vertex buffer
+pub struct VertexSyncBuffer<T> {
+    staging: Vec<T>,
+    commit: VertexBuffer<T>,
+}
+
+impl<T> VertexSyncBuffer<T>
+where
+    T: NoUninit + AnyBitPattern + WasmNotSend + 'static,
+{
+    pub fn new(size: usize) -> Self {
+        Self {
+            staging: Default::default(),
+            commit: VertexBuffer::new_empty(size),
+        }
+    }
+    /// anything past the capacity of the buffer is dropped
+    pub async fn finish(&mut self) -> Result<Option<(&VertexBuffer<T>, Range<u32>)>> {
+        if self.staging.is_empty() {
+            return Ok(None);
+        }
+        if self.staging.len() > self.commit.capacity() {
+            warn!(
+                "[{}] {} queued, only drawing the first [{}]",
+                self.staging.len(),
+                type_name::<T>(),
+                self.commit.capacity()
+            );
+            self.staging.truncate(self.commit.capacity());
+        }
+        let current_len = self.staging.len();
+        let commit = std::mem::replace(&mut self.staging, Vec::with_capacity(current_len));
+        self.commit
+            .write(0..current_len as _, move |data| data.copy_from_slice(&commit))
+            .await
+            .map(|_| Some((&self.commit, 0..current_len as u32)))
+    }
+}
+
 pub struct WithInstance<T> {
     pub instance: Instance,
     pub inner: T,
@@ -101,19 +162,47 @@ impl<T> WithInstance<T> {
     }
 }
 
+pub mod gizmo;
 pub mod model;
 pub mod node;
+pub mod particles;
 pub mod primitive;
+pub mod text;
+
+/// skinned instances are batched per skin, as every one of them has joint matrices of its own
+type QueueKey = (Primitive, Option<WithId<JointMatrices>>);
 
-#[derive(Default)]
 pub struct PassBuffer {
-    queue: BTreeMap<Primitive, InstanceSyncBuffer>,
+    queue: BTreeMap<QueueKey, InstanceSyncBuffer>,
+    unskinned: JointMatrices,
+    gizmos: VertexSyncBuffer<GizmoVertex>,
+    text: VertexSyncBuffer<GlyphInstance>,
+    particles: Vec<QueuedParticles>,
+}
+
+impl Default for PassBuffer {
+    fn default() -> Self {
+        Self {
+            queue: Default::default(),
+            unskinned: JointMatrices::unskinned(),
+            gizmos: VertexSyncBuffer::new(MAX_GIZMO_VERTICES),
+            text: VertexSyncBuffer::new(MAX_GLYPHS),
+            particles: Default::default(),
+        }
+    }
 }
 
 pub struct RenderPass<'pass, 'encoder> {
     pub(crate) buffer: &'pass mut PassBuffer,
     pub(crate) camera: Option<Camera>,
     pub(crate) camera_plugin: &'pass mut CameraPlugin,
+    pub(crate) pipelines: &'pass mut PipelineCache,
+    pub(crate) features: PassFeatures,
+    pub(crate) debug_view: DebugView,
+    pub(crate) glyph_atlas: &'pass GlyphAtlas,
+    pub(crate) g_buffer: &'pass GBuffer,
+    /// in pixels
+    pub(crate) screen_size: Vec2,
     pub(crate) pass: &'pass mut wgpu::RenderPass<'encoder>,
 }
 
@@ -122,7 +211,7 @@ pub trait DrawMe {
 }
 
 impl<'pass, 'renderer> RenderPass<'pass, 'renderer> {
-    pub async fn finish(self) -> Result<()> {
+    async fn write_camera(&self) -> Result<()> {
         if let Some(camera) = self.camera {
             self.camera_plugin
                 .buffer
@@ -132,18 +221,30 @@ impl<'pass, 'renderer> RenderPass<'pass, 'renderer> {
                 .await
                 .context("writing camera")?;
         }
+        Ok(())
+    }
 
-        self.buffer
-            .queue
+    /// draws every queued primitive `features_for` gives a pipeline to, the rest stay queued for a later pass
+    async fn draw_primitives(&mut self, features_for: impl Fn(MaterialFeatures) -> Option<PipelineFeatures>) -> Result<()> {
+        let PassBuffer { queue, unskinned, .. } = &mut *self.buffer;
+        queue
             .iter_mut()
+            .filter_map(|((primitive, joints), buffer)| {
+                features_for(primitive.material.as_ref().features).map(|features| {
+                    let joints = joints
+                        .as_ref()
+                        .map_or(&unskinned.bind_group, |joints| &joints.as_ref().bind_group);
+                    (features, (primitive, joints), buffer)
+                })
+            })
             .pipe(futures::stream::iter)
-            .filter_map(|(primitive, buffer)| async move {
+            .filter_map(|(features, primitive, buffer)| async move {
                 buffer
                     .finish()
                     .map(|finished| {
                         finished
                             .transpose()
-                            .map(|finished| finished.map(|finished| (primitive, finished)))
+                            .map(|finished| finished.map(|finished| (features, primitive, finished)))
                     })
                     .await
             })
@@ -153,12 +254,113 @@ impl<'pass, 'renderer> RenderPass<'pass, 'renderer> {
             .map(|flushed| {
                 flushed
                     .into_iter()
-                    .for_each(|(primitive, (instance_buffer, instances))| {
-                        self.pass.set_bind_group(3, instance_buffer, &[]);
-                        self.pass.draw_primitive_instanced(primitive, instances);
+                    .into_group_map_by(|(features, ..)| *features)
+                    .into_iter()
+                    // opaque geometry has to land in the depth buffer before anything is blended over it
+                    .sorted_by_key(|(features, _)| features.material.blend_mode)
+                    .for_each(|(features, primitives)| {
+                        self.pass
+                            .set_pipeline(self.pipelines.get_or_create(features));
+                        primitives
+                            .into_iter()
+                            .for_each(|(_, (primitive, joints), (instance_buffer, instances))| {
+                                self.pass.set_bind_group(3, instance_buffer, &[]);
+                                self.pass.set_bind_group(5, joints, &[]);
+                                self.pass.draw_primitive_instanced(primitive, instances);
+                            })
                     })
             })
     }
+
+    /// the first pass of a deferred frame, only opaque primitives are drawn and they go into the [GBuffer]
+    ///
+    /// everything else stays queued for [Self::finish] in the pass after lighting
+    pub async fn finish_g_buffer(mut self) -> Result<()> {
+        self.write_camera().await?;
+        if let Some(camera) = self.camera {
+            self.g_buffer
+                .update(&camera, self.screen_size)
+                .await
+                .context("updating g-buffer")?;
+        }
+        let pass = PassFeatures {
+            color_targets: ColorTargets::GBuffer,
+            ..self.features
+        };
+        self.draw_primitives(move |material| {
+            (material.blend_mode == BlendMode::Opaque).then_some(PipelineFeatures {
+                material: MaterialFeatures {
+                    entry_points: EntryPoints {
+                        fragment: G_BUFFER_ENTRY_POINT,
+                        ..material.entry_points
+                    },
+                    ..material
+                },
+                pass,
+            })
+        })
+        .await
+    }
+
+    pub async fn finish(mut self) -> Result<()> {
+        self.write_camera().await?;
+        let (debug_view, features) = (self.debug_view, self.features);
+        self.draw_primitives(move |material| {
+            Some(PipelineFeatures {
+                material: debug_view.material_features(material),
+                pass: debug_view.pass_features(features),
+            })
+        })
+        .await?;
+
+        // drawn last so they can be depth tested against the whole scene
+        if let Some((vertices, range)) = self
+            .buffer
+            .gizmos
+            .finish()
+            .await
+            .context("flushing gizmos")?
+        {
+            self.pass
+                .set_pipeline(self.pipelines.get_or_create(PipelineFeatures {
+                    material: GIZMO_MATERIAL,
+                    pass: PassFeatures {
+                        topology: wgpu::PrimitiveTopology::LineList,
+                        ..self.features
+                    },
+                }));
+            self.pass.set_vertex_buffer(0, vertices.as_ref().slice(..));
+            self.pass.draw(range, 0..1);
+        }
+
+        // blended over the scene without writing depth, so they only need to come after everything opaque
+        self.buffer
+            .particles
+            .drain(..)
+            .sorted_by_key(|queued| queued.material.blend_mode)
+            .for_each(|QueuedParticles { material, bind_group, count }| {
+                self.pass.set_pipeline(
+                    self.pipelines
+                        .get_or_create(PipelineFeatures { material, pass: self.features }),
+                );
+                self.pass.set_bind_group(1, &bind_group, &[]);
+                self.pass.draw(0..6, 0..count);
+            });
+
+        // text goes over everything else
+        if let Some((glyphs, range)) = self.buffer.text.finish().await.context("flushing text")? {
+            self.pass
+                .set_pipeline(self.pipelines.get_or_create(PipelineFeatures {
+                    material: TEXT_MATERIAL,
+                    pass: self.features,
+                }));
+            self.pass
+                .set_bind_group(1, &self.glyph_atlas.bind_group, &[]);
+            self.pass.set_vertex_buffer(0, glyphs.as_ref().slice(..));
+            self.pass.draw(0..6, range);
+        }
+        Ok(())
+    }
     pub fn draw<T: DrawMe>(&mut self, item: &T) -> Result<()> {
         item.draw_me(self)
             .with_context(|| format!("rendering [{}]", std::any::type_name::<T>()))
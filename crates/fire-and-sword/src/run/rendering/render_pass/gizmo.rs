@@ -0,0 +1,103 @@
+use {
+    super::RenderPass,
+    crate::run::rendering::{
+        model::load_gltf::Bounds,
+        pipeline::{BlendMode, EntryPoints, MaterialFeatures, VertexLayout},
+    },
+    itertools::Itertools,
+    shader_types::{
+        gizmo::GizmoVertex,
+        glam::{Affine3A, Mat4},
+        Color,
+        Vec3,
+    },
+    std::f32::consts::TAU,
+    tap::prelude::*,
+};
+
+/// two per line, anything past that in a single frame is dropped
+pub const MAX_GIZMO_VERTICES: usize = 1 << 16;
+const CIRCLE_SEGMENTS: usize = 24;
+
+pub const RED: Color = Color([1., 0., 0., 1.]);
+pub const GREEN: Color = Color([0., 1., 0., 1.]);
+pub const BLUE: Color = Color([0., 0., 1., 1.]);
+pub const YELLOW: Color = Color([1., 1., 0., 1.]);
+
+pub const GIZMO_MATERIAL: MaterialFeatures = MaterialFeatures {
+    blend_mode: BlendMode::Opaque,
+    double_sided: true,
+    entry_points: EntryPoints {
+        vertex: "gizmo::gizmo_vs",
+        fragment: "gizmo::gizmo_fs",
+    },
+    vertex_layout: VertexLayout::Gizmo,
+};
+
+/// corners are indexed by which of x, y and z sit on the far side
+fn box_edges(corners: [Vec3; 8]) -> impl Iterator<Item = (Vec3, Vec3)> {
+    (0..8)
+        .cartesian_product([1, 2, 4])
+        .filter(|(corner, axis)| corner & axis == 0)
+        .map(move |(corner, axis)| (corners[corner], corners[corner | axis]))
+}
+
+fn box_corners(corner: impl Fn(bool, bool, bool) -> Vec3) -> [Vec3; 8] {
+    std::array::from_fn(|idx| corner(idx & 1 != 0, idx & 2 != 0, idx & 4 != 0))
+}
+
+impl RenderPass<'_, '_> {
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: Color) {
+        self.buffer
+            .gizmos
+            .staging
+            .extend([from, to].map(|position| GizmoVertex {
+                position: position.extend(1.),
+                color,
+            }));
+    }
+
+    pub fn aabb(&mut self, transform: Affine3A, Bounds { min, max }: Bounds, color: Color) {
+        box_corners(|x, y, z| {
+            transform.transform_point3(Vec3::new(
+                if x { max.x } else { min.x },
+                if y { max.y } else { min.y },
+                if z { max.z } else { min.z },
+            ))
+        })
+        .pipe(box_edges)
+        .for_each(|(from, to)| self.line(from, to, color));
+    }
+
+    fn circle(&mut self, center: Vec3, (a, b): (Vec3, Vec3), radius: f32, color: Color) {
+        (0..=CIRCLE_SEGMENTS)
+            .map(|segment| (segment as f32 / CIRCLE_SEGMENTS as f32 * TAU).sin_cos())
+            .map(|(sin, cos)| center + (a * cos + b * sin) * radius)
+            .tuple_windows()
+            .for_each(|(from, to)| self.line(from, to, color));
+    }
+
+    /// one circle around each axis
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Color) {
+        [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)]
+            .into_iter()
+            .for_each(|plane| self.circle(center, plane, radius, color));
+    }
+
+    /// x, y and z in red, green and blue
+    pub fn axes(&mut self, transform: Affine3A, length: f32) {
+        [(Vec3::X, RED), (Vec3::Y, GREEN), (Vec3::Z, BLUE)]
+            .into_iter()
+            .for_each(|(axis, color)| self.line(transform.transform_point3(Vec3::ZERO), transform.transform_point3(axis * length), color));
+    }
+
+    /// the volume a camera with this view projection sees
+    pub fn frustum(&mut self, view_projection: Mat4, color: Color) {
+        view_projection.inverse().pipe(|inverse| {
+            // wgpu clip space depth goes from 0 to 1
+            box_corners(|x, y, z| inverse.project_point3(Vec3::new(if x { 1. } else { -1. }, if y { 1. } else { -1. }, if z { 1. } else { 0. })))
+                .pipe(box_edges)
+                .for_each(|(from, to)| self.line(from, to, color))
+        });
+    }
+}
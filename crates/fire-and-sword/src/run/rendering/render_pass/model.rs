@@ -1,15 +1,22 @@
 use {
     super::{DrawMe, RenderPass, WithInstance},
-    crate::run::rendering::model::load_gltf::Model,
+    crate::run::rendering::{identify::WithId, model::load_gltf::Model, skin::JointMatrices},
     anyhow::Context,
+    shader_types::Instance,
     tap::prelude::*,
 };
 
+/// a model deformed by a skin, its joint matrices place it in the world rather than the instance
+pub struct Skinned<'a> {
+    pub model: &'a Model,
+    pub joints: &'a WithId<JointMatrices>,
+}
+
 impl DrawMe for WithInstance<&Model> {
     fn draw_me<'a, 'b>(&self, pass: &mut RenderPass<'a, 'b>) -> anyhow::Result<()> {
         self.pipe(|WithInstance { instance, inner: model }| {
             model
-                .primitives
+                .primitives_for(pass.camera.as_ref(), instance)
                 .iter()
                 .map(|primitive| WithInstance {
                     instance: *instance,
@@ -20,3 +27,24 @@ impl DrawMe for WithInstance<&Model> {
         .context("drawing model")
     }
 }
+
+impl DrawMe for WithInstance<Skinned<'_>> {
+    fn draw_me<'a, 'b>(&self, pass: &mut RenderPass<'a, 'b>) -> anyhow::Result<()> {
+        self.pipe(
+            |WithInstance {
+                 instance,
+                 inner: Skinned { model, joints },
+             }| {
+                // the instance still picks the level of detail
+                model
+                    .primitives_for(pass.camera.as_ref(), instance)
+                    .iter()
+                    .for_each(|primitive| {
+                        pass.buffer
+                            .queue_primitive(primitive, Some(joints), Instance::default())
+                    })
+            },
+        )
+        .pipe(Ok)
+    }
+}
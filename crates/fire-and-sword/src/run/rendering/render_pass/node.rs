@@ -1,15 +1,25 @@
 use {
-    super::{DrawMe, RenderPass, WithInstance},
+    super::{
+        gizmo::{GREEN, RED, YELLOW},
+        model::Skinned,
+        DrawMe,
+        RenderPass,
+        WithInstance,
+    },
     crate::run::rendering::scene::{Node, NodeData, WithTransform},
     anyhow::Context,
     shader_types::{
-        glam::{Affine3A, Vec4Swizzles},
+        glam::{Affine3A, Mat4, Vec4Swizzles},
         Instance,
     },
     tap::prelude::*,
     tracing::trace,
 };
 
+const GIZMO_AXES_LENGTH: f32 = 0.5;
+/// emitters spawning from a single point still get a visible marker
+const GIZMO_EMITTER_RADIUS: f32 = 0.1;
+
 #[extension_traits::extension(pub trait TransformInstanceExt)]
 impl Instance {
     fn transformed(self, transform: &Affine3A) -> Self {
@@ -31,18 +41,42 @@ impl WithInstance<&WithTransform<&Node>> {
             .pipe(|Self { instance, inner: node }| {
                 node.pipe(
                     |WithTransform {
-                         inner: Node { data, children },
+                         inner: Node { data, skin, children, .. },
                          transform: parent_transform,
                      }| {
+                        let instance_transform = instance
+                            .transformed(parent_transform)
+                            .pipe(|Instance { position, rotation }| Affine3A::from_rotation_translation(rotation, position.xyz()));
                         (match data {
                             Some(parent) => match parent {
-                                NodeData::Camera => Ok(()),
-                                NodeData::Model(model) => WithInstance {
-                                    instance: instance.transformed(parent_transform),
-                                    inner: model,
+                                NodeData::Camera(projection) => Ok(if pass.debug_view.shows_gizmos() {
+                                    pass.axes(instance_transform, GIZMO_AXES_LENGTH);
+                                    pass.frustum(*projection * Mat4::from(instance_transform.inverse()), YELLOW);
+                                }),
+                                NodeData::Model(model) => match skin {
+                                    Some(skin) => WithInstance {
+                                        instance: instance.transformed(parent_transform),
+                                        inner: Skinned { model, joints: &skin.matrices },
+                                    }
+                                    .draw_me(pass),
+                                    None => WithInstance {
+                                        instance: instance.transformed(parent_transform),
+                                        inner: model,
+                                    }
+                                    .draw_me(pass),
                                 }
-                                .draw_me(pass)
-                                .tap_ok_dbg(|_| trace!("drawing {model:?} at [{:?}] ({instance:?})", instance.transformed(parent_transform))),
+                                .tap_ok_dbg(|_| trace!("drawing {model:?} at [{:?}] ({instance:?})", instance.transformed(parent_transform)))
+                                .tap_ok(|_| {
+                                    if pass.debug_view.shows_gizmos() {
+                                        pass.aabb(instance_transform, model.bounds, GREEN);
+                                    }
+                                }),
+                                NodeData::Emitter(emitter) => Ok({
+                                    pass.particles(emitter);
+                                    if pass.debug_view.shows_gizmos() {
+                                        pass.sphere(instance_transform.translation.into(), emitter.params.radius.max(GIZMO_EMITTER_RADIUS), RED);
+                                    }
+                                }),
                             },
                             None => Ok(()),
                         })
@@ -0,0 +1,21 @@
+use {
+    super::RenderPass,
+    crate::run::rendering::{particles::ParticleEmitter, pipeline::MaterialFeatures},
+};
+
+/// an emitter queued for drawing, its bind group outlives the scene borrow of a single frame
+pub struct QueuedParticles {
+    pub material: MaterialFeatures,
+    pub bind_group: wgpu::BindGroup,
+    pub count: u32,
+}
+
+impl RenderPass<'_, '_> {
+    pub fn particles(&mut self, emitter: &ParticleEmitter) {
+        self.buffer.particles.push(QueuedParticles {
+            material: emitter.params.blend.material(),
+            bind_group: emitter.draw_bind_group.clone(),
+            count: emitter.capacity(),
+        });
+    }
+}
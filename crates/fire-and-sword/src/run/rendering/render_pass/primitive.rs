@@ -1,20 +1,29 @@
 use {
-    super::{DrawMe, InstanceSyncBuffer, RenderPass, WithInstance},
-    crate::run::rendering::model::Primitive,
+    super::{DrawMe, InstanceSyncBuffer, PassBuffer, RenderPass, WithInstance},
+    crate::run::rendering::{identify::WithId, model::Primitive, skin::JointMatrices},
+    shader_types::Instance,
     tap::prelude::*,
 };
 
 pub const MAX_INSTANCES: usize = 1024;
 
-impl DrawMe for WithInstance<&Primitive> {
-    fn draw_me<'a, 'b>(&self, RenderPass { buffer, .. }: &mut RenderPass<'a, 'b>) -> anyhow::Result<()> {
-        self.pipe(|WithInstance { instance, inner: primitive }| match buffer.queue.get_mut(primitive) {
-            Some(exists) => exists.as_mut().push(*instance),
-            None => buffer
+impl PassBuffer {
+    /// `joints` for instances of a skinned model
+    pub(crate) fn queue_primitive(&mut self, primitive: &Primitive, joints: Option<&WithId<JointMatrices>>, instance: Instance) {
+        let key = (primitive.clone(), joints.cloned());
+        match self.queue.get_mut(&key) {
+            Some(exists) => exists.as_mut().push(instance),
+            None => self
                 .queue
-                .insert((*primitive).clone(), InstanceSyncBuffer::new_init(MAX_INSTANCES, vec![*instance]))
+                .insert(key, InstanceSyncBuffer::new_init(MAX_INSTANCES, vec![instance]))
                 .pipe(|_| ()),
-        })
-        .pipe(Ok)
+        }
+    }
+}
+
+impl DrawMe for WithInstance<&Primitive> {
+    fn draw_me<'a, 'b>(&self, RenderPass { buffer, .. }: &mut RenderPass<'a, 'b>) -> anyhow::Result<()> {
+        self.pipe(|WithInstance { instance, inner: primitive }| buffer.queue_primitive(primitive, None, *instance))
+            .pipe(Ok)
     }
 }
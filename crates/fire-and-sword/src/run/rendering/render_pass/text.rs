@@ -0,0 +1,54 @@
+use {
+    super::RenderPass,
+    crate::run::rendering::pipeline::{BlendMode, EntryPoints, MaterialFeatures, VertexLayout},
+    shader_types::{
+        glam::{Vec3, Vec4Swizzles},
+        text::GlyphInstance,
+        Color,
+        Vec2,
+        Vec4,
+    },
+};
+
+pub const MAX_GLYPHS: usize = 4096;
+
+pub const TEXT_MATERIAL: MaterialFeatures = MaterialFeatures {
+    blend_mode: BlendMode::AlphaBlend,
+    double_sided: true,
+    entry_points: EntryPoints {
+        vertex: "text::text_vs",
+        fragment: "text::text_fs",
+    },
+    vertex_layout: VertexLayout::Text,
+};
+
+impl RenderPass<'_, '_> {
+    /// `position` is the top left corner of the text and `size` its height, both in pixels
+    pub fn text(&mut self, position: Vec2, size: f32, color: Color, text: &str) {
+        let screen_size = self.screen_size;
+        let to_clip = |pixels: Vec2| pixels / screen_size * Vec2::new(2., -2.) + Vec2::new(-1., 1.);
+        self.buffer.text.staging.extend(
+            self.glyph_atlas
+                .layout(text, position, size)
+                .map(|glyph| GlyphInstance {
+                    rect: Vec4::from((to_clip(glyph.rect.xy()), to_clip(glyph.rect.zw()))),
+                    tex_coords: glyph.tex_coords,
+                    color,
+                }),
+        );
+    }
+
+    /// centered just above a point in the world, skipped when the point is behind the camera
+    pub fn world_text(&mut self, position: Vec3, size: f32, color: Color, text: &str) {
+        let Some(camera) = self.camera else {
+            return;
+        };
+        let clip = camera.get_view_projection() * position.extend(1.);
+        if clip.w <= 0. {
+            return;
+        }
+        let pixels = (clip.xy() / clip.w * Vec2::new(0.5, -0.5) + 0.5) * self.screen_size;
+        let width = self.glyph_atlas.width(text, size);
+        self.text(pixels - Vec2::new(width / 2., size), size, color, text);
+    }
+}
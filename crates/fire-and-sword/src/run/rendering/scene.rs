@@ -1,16 +1,91 @@
 use {
-    super::model::load_gltf::{GltfImport, Model},
+    super::{
+        animation::{Animations, Pose, RestPose},
+        model::{
+            load_gltf::{GltfImport, Model},
+            Primitive,
+        },
+        particles::ParticleEmitter,
+        skin::Skin,
+    },
     anyhow::{Context, Result},
     itertools::Itertools,
     nonempty::NonEmpty,
-    shader_types::{glam::Affine3A, Quat, Vec3},
+    shader_types::{
+        camera::Z_FAR,
+        glam::{Affine3A, Mat4},
+        Quat,
+        Vec3,
+    },
+    std::{collections::HashMap, convert::Infallible, str::FromStr, time::Duration},
     tap::prelude::*,
 };
 
+/// anything that parses as a number is an index, everything else a name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl FromStr for SceneSelector {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse()
+            .map(Self::Index)
+            .unwrap_or_else(|_| Self::Name(s.to_owned())))
+    }
+}
+
+impl SceneSelector {
+    /// the error lists every scene the document does have
+    pub fn find<'a>(&self, document: &'a gltf::Document) -> anyhow::Result<gltf::Scene<'a>> {
+        document
+            .scenes()
+            .find(|scene| match self {
+                Self::Index(index) => scene.index() == *index,
+                Self::Name(name) => scene.name() == Some(name.as_str()),
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "no scene {self:?}, available: [{}]",
+                    document
+                        .scenes()
+                        .map(|scene| format!("#{} {}", scene.index(), scene.name().unwrap_or("(unnamed)")))
+                        .join(", ")
+                )
+            })
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 pub enum NodeData {
-    Camera,
+    /// projection of a camera placed in the scene
+    Camera(Mat4),
     Model(Model),
+    Emitter(ParticleEmitter),
+}
+
+impl NodeData {
+    fn camera(camera: gltf::Camera<'_>) -> Self {
+        Self::Camera(match camera.projection() {
+            gltf::camera::Projection::Perspective(perspective) => Mat4::perspective_rh(
+                perspective.yfov(),
+                perspective.aspect_ratio().unwrap_or(1.),
+                perspective.znear(),
+                perspective.zfar().unwrap_or(Z_FAR),
+            ),
+            gltf::camera::Projection::Orthographic(orthographic) => Mat4::orthographic_rh(
+                -orthographic.xmag(),
+                orthographic.xmag(),
+                -orthographic.ymag(),
+                orthographic.ymag(),
+                orthographic.znear(),
+                orthographic.zfar(),
+            ),
+        })
+    }
 }
 
 pub struct WithTransform<T> {
@@ -40,58 +115,236 @@ impl<T> WithTransform<T> {
 }
 
 pub struct Node {
+    /// of the glTF node this came from, animations find the nodes they drive by it
+    pub index: Option<usize>,
+    /// the transform the node is loaded with, animations pose it from here
+    pub rest: RestPose,
     pub data: Option<NodeData>,
+    /// deforms the model of this node, [None] for anything rigid
+    pub skin: Option<Skin>,
     pub children: Vec<WithTransform<Self>>,
 }
 
 impl Node {
+    /// emitters get a node of their own, so a mesh and an emitter can share a glTF node
+    fn emitter(emitter: ParticleEmitter) -> WithTransform<Self> {
+        WithTransform {
+            inner: Node {
+                index: None,
+                rest: RestPose::default(),
+                data: Some(NodeData::Emitter(emitter)),
+                skin: None,
+                children: vec![],
+            },
+            transform: Affine3A::IDENTITY,
+        }
+    }
+
     fn load(context: &GltfImport, node_data: gltf::Node<'_>) -> Result<WithTransform<Self>> {
-        None.or_else(|| node_data.camera().map(|_| NodeData::Camera.pipe(Ok)))
+        None.or_else(|| node_data.camera().map(NodeData::camera).map(Some).map(Ok))
             .or_else(|| {
                 node_data
                     .mesh()
-                    .map(|m| Model::load(context, m).map(NodeData::Model))
+                    .map(|mesh| Model::load_for_node(context, &node_data, mesh).map(|model| model.map(NodeData::Model)))
             })
             .transpose()
+            .map(Option::flatten)
             .and_then(|data| {
-                node_data
-                    .children()
-                    .map(|child| Self::load(context, child))
+                let skin = node_data
+                    .skin()
+                    .filter(|_| node_data.mesh().is_some())
+                    .map(|skin| {
+                        let index = skin.index();
+                        Skin::load(context, skin).with_context(|| format!("loading skin #{index}"))
+                    })
+                    .transpose()?;
+                ParticleEmitter::from_extras(node_data.name().unwrap_or("(unnamed)"), node_data.extras())
+                    .map(Self::emitter)
+                    .map(Ok)
+                    .into_iter()
+                    .chain(node_data.children().map(|child| Self::load(context, child)))
                     .collect::<Result<Vec<_>>>()
                     .context("loading children failed")
-                    .map(|children| Node { data, children })
-                    .map(|node| WithTransform {
-                        transform: node_data
+                    .map(|children| Node {
+                        index: Some(node_data.index()),
+                        rest: node_data
                             .transform()
                             .decomposed()
-                            .pipe(|(translation, rotation, scale)| (Vec3::from(translation), Quat::from_array(rotation), Vec3::from(scale)))
-                            .pipe(|(translation, rotation, scale)| Affine3A::from_scale_rotation_translation(scale, rotation, translation)),
+                            .pipe(|(translation, rotation, scale)| RestPose {
+                                translation: Vec3::from(translation),
+                                rotation: Quat::from_array(rotation),
+                                scale: Vec3::from(scale),
+                            }),
+                        data,
+                        skin,
+                        children,
+                    })
+                    .map(|node| WithTransform {
+                        transform: Pose::default().apply(&node.rest),
                         inner: node,
                     })
             })
     }
 }
 
+impl WithTransform<Node> {
+    /// every emitter at or below this node, with its transform in the space `parent` maps to
+    pub fn emitters(&self, parent: Affine3A) -> Vec<(Affine3A, &ParticleEmitter)> {
+        let transform = parent * self.transform;
+        self.inner
+            .data
+            .iter()
+            .filter_map(|data| match data {
+                NodeData::Emitter(emitter) => Some((transform, emitter)),
+                _ => None,
+            })
+            .chain(
+                self.inner
+                    .children
+                    .iter()
+                    .flat_map(|child| child.emitters(transform)),
+            )
+            .collect()
+    }
+
+    /// every primitive at or below this node, lower levels of detail included
+    pub fn primitives(&self) -> Vec<&Primitive> {
+        self.inner
+            .data
+            .iter()
+            .flat_map(|data| match data {
+                NodeData::Model(model) => model
+                    .primitives
+                    .iter()
+                    .chain(model.lods.iter().flat_map(|lod| lod.primitives.iter()))
+                    .collect_vec(),
+                _ => vec![],
+            })
+            .chain(
+                self.inner
+                    .children
+                    .iter()
+                    .flat_map(|child| child.primitives()),
+            )
+            .collect()
+    }
+
+    /// by glTF index, nodes that didn't come from one are left out
+    pub fn world_transforms(&self, parent: Affine3A) -> Vec<(usize, Affine3A)> {
+        let transform = parent * self.transform;
+        self.inner
+            .index
+            .map(|index| (index, transform))
+            .into_iter()
+            .chain(
+                self.inner
+                    .children
+                    .iter()
+                    .flat_map(|child| child.world_transforms(transform)),
+            )
+            .collect()
+    }
+
+    pub fn skins(&self) -> Vec<&Skin> {
+        self.inner
+            .skin
+            .iter()
+            .chain(self.inner.children.iter().flat_map(|child| child.skins()))
+            .collect()
+    }
+
+    /// nodes the clip doesn't drive go back to their rest pose
+    fn apply_poses(&mut self, poses: &HashMap<usize, Pose>) {
+        if let Some(index) = self.inner.index {
+            self.transform = poses
+                .get(&index)
+                .copied()
+                .unwrap_or_default()
+                .apply(&self.inner.rest);
+        }
+        self.inner
+            .children
+            .iter_mut()
+            .for_each(|child| child.apply_poses(poses));
+    }
+}
+
 pub struct Scene {
     pub nodes: NonEmpty<WithTransform<Node>>,
+    /// [None] for scenes that don't move on their own
+    pub animations: Option<Animations>,
 }
 
 impl Scene {
-    pub fn load_all(context: &GltfImport) -> Result<NonEmpty<Self>> {
-        context
-            .0
-            .scenes()
-            .map(|scene| {
-                scene
-                    .nodes()
-                    .map(|node| Node::load(context, node))
-                    .collect::<Result<Vec<_>>>()
-                    .context("loading nodes for a scene")
-                    .and_then(|nodes| NonEmpty::from_vec(nodes).context("scenes without nodes are not supported"))
-            })
-            .map_ok(|nodes| Self { nodes })
+    pub fn emitters(&self) -> Vec<(Affine3A, &ParticleEmitter)> {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.emitters(Affine3A::IDENTITY))
+            .collect()
+    }
+
+    /// writes the joint matrices of every skin for where its joints are right now
+    pub async fn update_skins(&self) -> Result<()> {
+        let skins = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.skins())
+            .collect_vec();
+        if skins.is_empty() {
+            return Ok(());
+        }
+        let world = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.world_transforms(Affine3A::IDENTITY))
+            .collect::<HashMap<_, _>>();
+        skins
+            .into_iter()
+            .map(|skin| skin.update(&world))
+            .pipe(futures::future::try_join_all)
+            .await
+            .map(|_| ())
+    }
+
+    /// advances the playing clip and moves the nodes it drives
+    pub fn animate(&mut self, by: Duration) {
+        if let Some(animations) = self.animations.as_mut() {
+            animations.advance(by);
+            let poses = animations.pose();
+            self.nodes
+                .iter_mut()
+                .for_each(|node| node.apply_poses(&poses));
+        }
+    }
+
+    /// every model becomes a node of its own at the origin
+    pub fn from_models(models: NonEmpty<Model>) -> Self {
+        Self {
+            nodes: models.map(|model| WithTransform {
+                inner: Node {
+                    index: None,
+                    rest: RestPose::default(),
+                    data: Some(NodeData::Model(model)),
+                    skin: None,
+                    children: vec![],
+                },
+                transform: Affine3A::IDENTITY,
+            }),
+            animations: None,
+        }
+    }
+
+    pub fn load(context: &GltfImport, scene: gltf::Scene<'_>) -> Result<Self> {
+        scene
+            .nodes()
+            .map(|node| Node::load(context, node))
             .collect::<Result<Vec<_>>>()
-            .context("loading all scenes")
-            .and_then(|scenes| NonEmpty::from_vec(scenes).context("file should have at least one scene"))
+            .context("loading nodes for a scene")
+            .and_then(|nodes| NonEmpty::from_vec(nodes).context("scenes without nodes are not supported"))
+            .and_then(|nodes| {
+                Animations::load(context)
+                    .context("loading animations")
+                    .map(|animations| Self { nodes, animations })
+            })
     }
 }
@@ -0,0 +1,59 @@
+use {
+    super::wgpu_ext::global_context::device,
+    anyhow::{Context, Result},
+    tap::prelude::*,
+    tracing::info,
+};
+
+/// SPIR-V produced by `just rebuild-shaders`
+pub static SHADERS_SPV: &[u8] = include_bytes!("../../../../../shaders.spv");
+/// where `just rebuild-shaders` writes [SHADERS_SPV], watched for hot reloading
+pub const SHADERS_SPV_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../shaders.spv");
+
+/// how the SPIR-V produced by rust-gpu reaches the driver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderPath {
+    /// handed to the driver untouched, needs [wgpu::Features::SPIRV_SHADER_PASSTHROUGH]
+    Passthrough,
+    /// translated by naga, works on software adapters and WebGPU
+    Naga,
+}
+
+impl ShaderPath {
+    pub fn for_adapter(adapter: &wgpu::Adapter) -> Self {
+        match adapter
+            .features()
+            .contains(wgpu::Features::SPIRV_SHADER_PASSTHROUGH)
+        {
+            true => Self::Passthrough,
+            false => Self::Naga,
+        }
+        .tap(|path| info!("adapter [{}] gets shaders through {path:?}", adapter.get_info().name))
+    }
+
+    pub fn required_features(self) -> wgpu::Features {
+        match self {
+            ShaderPath::Passthrough => wgpu::Features::SPIRV_SHADER_PASSTHROUGH,
+            ShaderPath::Naga => wgpu::Features::empty(),
+        }
+    }
+
+    /// validation errors are reported instead of going to the uncaptured error handler
+    pub async fn create_module(self, label: &str, spirv: &[u8]) -> Result<wgpu::ShaderModule> {
+        let source = wgpu::util::make_spirv_raw(spirv);
+        device().push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = match self {
+            // SAFETY: the module comes straight out of rust-gpu, which emits valid SPIR-V
+            ShaderPath::Passthrough => unsafe { device().create_shader_module_spirv(&wgpu::ShaderModuleDescriptorSpirV { label: Some(label), source }) },
+            ShaderPath::Naga => device().create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::SpirV(source),
+            }),
+        };
+        device()
+            .pop_error_scope()
+            .await
+            .map_or(Ok(module), |error| Err(anyhow::anyhow!("{error}")))
+            .with_context(|| format!("compiling shader module [{label}] through {self:?}"))
+    }
+}
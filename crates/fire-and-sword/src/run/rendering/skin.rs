@@ -0,0 +1,112 @@
+use {
+    super::{
+        identify::WithId,
+        model::load_gltf::GltfImport,
+        wgpu_ext::{
+            bind_group::{storage_entry, HasBindGroup},
+            buffer::storage::StorageBuffer,
+            global_context::device,
+        },
+    },
+    crate::bind_group_layout,
+    anyhow::{Context, Result},
+    itertools::Itertools,
+    shader_types::glam::{Affine3A, Mat4},
+    std::collections::HashMap,
+    tap::prelude::*,
+};
+
+bind_group_layout!(
+    JointMatrices,
+    wgpu::BindGroupLayoutDescriptor {
+        label: struct_label!(),
+        entries: &[
+            // JOINTS
+            storage_entry(0, wgpu::ShaderStages::VERTEX, true),
+        ],
+    }
+);
+
+/// what `main_vs` skins vertices with, one buffer for every skinned instance
+pub struct JointMatrices {
+    buffer: StorageBuffer<Mat4>,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+impl JointMatrices {
+    fn new(joints: usize) -> Self {
+        let buffer = StorageBuffer::<Mat4>::new_empty(joints.max(1));
+        Self {
+            bind_group: device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: struct_label!(),
+                layout: Self::bind_group_layout(),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_ref().as_entire_binding(),
+                }],
+            }),
+            buffer,
+        }
+    }
+
+    /// bound for everything drawn without a skin, vertices without weights never read it
+    pub fn unskinned() -> Self {
+        Self::new(1)
+    }
+
+    async fn write(&self, matrices: Vec<Mat4>) -> Result<()> {
+        self.buffer
+            .write(0..matrices.len() as _, move |buf| buf.copy_from_slice(&matrices))
+            .await
+    }
+}
+
+/// a glTF skin attached to a node, the joints are other nodes of the scene
+pub struct Skin {
+    /// glTF node indices, in the order `JOINTS_0` counts them
+    joints: Vec<usize>,
+    inverse_bind_matrices: Vec<Mat4>,
+    pub matrices: WithId<JointMatrices>,
+}
+
+impl Skin {
+    /// skins without inverse bind matrices are bound at the joints as they are
+    pub fn load(GltfImport { buffers, .. }: &GltfImport, skin: gltf::Skin<'_>) -> Result<Self> {
+        let joints = skin.joints().map(|joint| joint.index()).collect_vec();
+        let inverse_bind_matrices = skin
+            .reader(|buffer| buffers.get(buffer.index()).map(|data| data.as_ref()))
+            .read_inverse_bind_matrices()
+            .map(|matrices| {
+                matrices
+                    .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                    .collect_vec()
+            })
+            .unwrap_or_else(|| vec![Mat4::IDENTITY; joints.len()]);
+        if inverse_bind_matrices.len() != joints.len() {
+            anyhow::bail!("[{}] joints but [{}] inverse bind matrices", joints.len(), inverse_bind_matrices.len());
+        }
+        Ok(Self {
+            #[allow(deprecated)]
+            matrices: WithId::register(JointMatrices::new(joints.len())),
+            joints,
+            inverse_bind_matrices,
+        })
+    }
+
+    /// `world` holds the transform of every node by its glTF index, joints missing from it fall back to the identity
+    pub async fn update(&self, world: &HashMap<usize, Affine3A>) -> Result<()> {
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(joint, inverse_bind)| {
+                world
+                    .get(joint)
+                    .map(|transform| Mat4::from(*transform) * *inverse_bind)
+                    .unwrap_or(Mat4::IDENTITY)
+            })
+            .collect_vec()
+            .pipe(|matrices| self.matrices.as_ref().write(matrices))
+            .await
+            .context("writing joint matrices")
+    }
+}
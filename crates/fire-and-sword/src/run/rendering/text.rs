@@ -0,0 +1,203 @@
+use {
+    super::{
+        texture::Texture,
+        wgpu_ext::{bind_group::HasBindGroup, global_context::device},
+    },
+    crate::bind_group_layout,
+    ab_glyph::{Font, FontRef, ScaleFont},
+    anyhow::{Context, Result},
+    image::{Rgba, RgbaImage},
+    shader_types::{Vec2, Vec4},
+    std::collections::HashMap,
+    tap::prelude::*,
+    tracing::debug,
+};
+
+/// DejaVu Sans Mono, see `assets/fonts/DejaVuSansMono-LICENSE.txt`
+pub static FONT: &[u8] = include_bytes!("../../../../../assets/fonts/DejaVuSansMono.ttf");
+/// glyphs are rasterized once at this height and scaled when drawn
+pub const ATLAS_GLYPH_SIZE: f32 = 32.;
+const ATLAS_WIDTH: u32 = 512;
+/// keeps linear filtering from bleeding neighbouring glyphs in
+const ATLAS_PADDING: u32 = 1;
+const FALLBACK: char = '?';
+
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphInfo {
+    /// left, top, right, bottom in the atlas
+    pub tex_coords: Vec4,
+    /// from the top left of the line to the top left of the glyph
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub advance: f32,
+}
+
+/// printable ascii rasterized into a single texture
+pub struct GlyphAtlas {
+    pub texture: Texture,
+    pub(crate) bind_group: wgpu::BindGroup,
+    glyphs: HashMap<char, GlyphInfo>,
+    line_height: f32,
+}
+
+bind_group_layout!(
+    GlyphAtlas,
+    wgpu::BindGroupLayoutDescriptor {
+        label: struct_label!(),
+        entries: &[
+            // ATLAS
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+    }
+);
+
+/// a glyph placed on screen, in pixels from the top left corner
+pub struct PlacedGlyph {
+    /// left, top, right, bottom
+    pub rect: Vec4,
+    pub tex_coords: Vec4,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> Result<Self> {
+        let font = FontRef::try_from_slice(FONT).context("loading font")?;
+        let scaled = font.as_scaled(ATLAS_GLYPH_SIZE);
+        let outlined = (' '..='~')
+            .map(|character| {
+                scaled
+                    .scaled_glyph(character)
+                    .tap_mut(|glyph| glyph.position = ab_glyph::point(0., scaled.ascent()))
+                    .pipe(|glyph| (character, scaled.h_advance(glyph.id), font.outline_glyph(glyph)))
+            })
+            .collect::<Vec<_>>();
+
+        // shelf packing, glyphs go left to right and wrap onto a new row
+        let (mut cursor, mut row_height) = ((0, 0), 0);
+        let placed = outlined
+            .iter()
+            .map(|(character, advance, outline)| {
+                let bounds = outline.as_ref().map(|outline| outline.px_bounds());
+                let (width, height) = bounds
+                    .map(|bounds| (bounds.width().ceil() as u32, bounds.height().ceil() as u32))
+                    .unwrap_or_default();
+                if cursor.0 + width + ATLAS_PADDING > ATLAS_WIDTH {
+                    cursor = (0, cursor.1 + row_height + ATLAS_PADDING);
+                    row_height = 0;
+                }
+                let origin = cursor;
+                cursor.0 += width + ATLAS_PADDING;
+                row_height = row_height.max(height);
+                (*character, *advance, bounds, origin, (width, height))
+            })
+            .collect::<Vec<_>>();
+        let atlas_height = cursor.1 + row_height + ATLAS_PADDING;
+
+        let mut image = RgbaImage::from_pixel(ATLAS_WIDTH, atlas_height, Rgba([255, 255, 255, 0]));
+        outlined
+            .iter()
+            .zip(&placed)
+            .filter_map(|((_, _, outline), (.., origin, _))| outline.as_ref().map(|outline| (outline, origin)))
+            .for_each(|(outline, (x, y))| {
+                outline
+                    .draw(|glyph_x, glyph_y, coverage| image.put_pixel(x + glyph_x, y + glyph_y, Rgba([255, 255, 255, (coverage.clamp(0., 1.) * 255.) as u8])))
+            });
+        debug!("rasterized [{}] glyphs into a {ATLAS_WIDTH}x{atlas_height} atlas", placed.len());
+
+        let atlas_size = Vec2::new(ATLAS_WIDTH as f32, atlas_height as f32);
+        let texture = Texture::from_image(&image::DynamicImage::ImageRgba8(image), Some("glyph atlas"));
+        Ok(Self {
+            bind_group: device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: struct_label!(),
+                layout: Self::bind_group_layout(),
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+            }),
+            texture,
+            glyphs: placed
+                .into_iter()
+                .map(|(character, advance, bounds, (x, y), (width, height))| {
+                    let min = Vec2::new(x as f32, y as f32) / atlas_size;
+                    let max = Vec2::new((x + width) as f32, (y + height) as f32) / atlas_size;
+                    (
+                        character,
+                        GlyphInfo {
+                            tex_coords: Vec4::new(min.x, min.y, max.x, max.y),
+                            offset: bounds
+                                .map(|bounds| Vec2::new(bounds.min.x, bounds.min.y))
+                                .unwrap_or_default(),
+                            size: Vec2::new(width as f32, height as f32),
+                            advance,
+                        },
+                    )
+                })
+                .collect(),
+            line_height: scaled.height() + scaled.line_gap(),
+        })
+    }
+
+    fn glyph(&self, character: char) -> Option<&GlyphInfo> {
+        self.glyphs
+            .get(&character)
+            .or_else(|| self.glyphs.get(&FALLBACK))
+    }
+
+    /// widest line of the text, in pixels
+    pub fn width(&self, text: &str, size: f32) -> f32 {
+        text.lines()
+            .map(|line| {
+                line.chars()
+                    .filter_map(|character| self.glyph(character))
+                    .map(|glyph| glyph.advance)
+                    .sum::<f32>()
+            })
+            .fold(0., f32::max)
+            * size
+            / ATLAS_GLYPH_SIZE
+    }
+
+    /// lays out every glyph that has something to draw, starting with the top left corner at `origin`
+    pub fn layout<'a>(&'a self, text: &'a str, origin: Vec2, size: f32) -> impl Iterator<Item = PlacedGlyph> + 'a {
+        let scale = size / ATLAS_GLYPH_SIZE;
+        text.lines().enumerate().flat_map(move |(line, text)| {
+            text.chars()
+                .filter_map(|character| self.glyph(character))
+                .scan(0., |pen, glyph| {
+                    let at = *pen;
+                    *pen += glyph.advance;
+                    Some((at, glyph))
+                })
+                .filter(|(_, glyph)| glyph.size != Vec2::ZERO)
+                .map(move |(pen, glyph)| {
+                    let top_left = origin + (Vec2::new(pen, line as f32 * self.line_height) + glyph.offset) * scale;
+                    let bottom_right = top_left + glyph.size * scale;
+                    PlacedGlyph {
+                        rect: Vec4::new(top_left.x, top_left.y, bottom_right.x, bottom_right.y),
+                        tex_coords: glyph.tex_coords,
+                    }
+                })
+        })
+    }
+}
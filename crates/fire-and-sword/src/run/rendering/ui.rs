@@ -0,0 +1,72 @@
+use {
+    super::wgpu_ext::global_context::{device, queue},
+    egui_wgpu::ScreenDescriptor,
+    tap::prelude::*,
+};
+
+/// everything egui produced for one frame
+pub struct UiFrame {
+    pub primitives: Vec<egui::ClippedPrimitive>,
+    pub textures_delta: egui::TexturesDelta,
+    pub pixels_per_point: f32,
+}
+
+/// draws egui in its own pass on top of the finished frame
+pub struct UiRenderer {
+    renderer: egui_wgpu::Renderer,
+}
+
+impl UiRenderer {
+    pub fn new(color_format: wgpu::TextureFormat) -> Self {
+        Self {
+            renderer: egui_wgpu::Renderer::new(device(), color_format, None, 1, false),
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        (width, height): (u32, u32),
+        UiFrame {
+            primitives,
+            textures_delta,
+            pixels_per_point,
+        }: UiFrame,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+    ) {
+        let screen = ScreenDescriptor {
+            size_in_pixels: [width, height],
+            pixels_per_point,
+        };
+        textures_delta
+            .set
+            .iter()
+            .for_each(|(id, delta)| self.renderer.update_texture(device(), queue(), *id, delta));
+        // only paint callbacks produce these, they have to run before the pass that uses them
+        self.renderer
+            .update_buffers(device(), queue(), encoder, &primitives, &screen)
+            .pipe(|callbacks| queue().submit(callbacks));
+        encoder
+            .begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("ui pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes,
+                occlusion_query_set: None,
+            })
+            .forget_lifetime()
+            .pipe(|mut pass| self.renderer.render(&mut pass, &primitives, &screen));
+        textures_delta
+            .free
+            .iter()
+            .for_each(|id| self.renderer.free_texture(id));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod bind_group;
 pub mod buffer;
+pub mod compute;
 pub mod global_context;
+pub mod profiler;
@@ -2,6 +2,69 @@ use wgpu::BindGroupLayout;
 
 pub trait HasBindGroup {
     fn bind_group_layout() -> &'static BindGroupLayout;
+    /// the entries the layout was created from, wgpu doesn't let us read them back
+    fn bind_group_layout_entries() -> &'static [wgpu::BindGroupLayoutEntry];
+}
+
+/// one bind group slot of a pipeline layout
+#[derive(Debug, Clone, Copy)]
+pub struct BindGroupSet {
+    pub name: &'static str,
+    pub layout: &'static BindGroupLayout,
+    pub entries: &'static [wgpu::BindGroupLayoutEntry],
+}
+
+impl BindGroupSet {
+    pub fn of<T: HasBindGroup>() -> Self {
+        Self {
+            name: std::any::type_name::<T>(),
+            layout: T::bind_group_layout(),
+            entries: T::bind_group_layout_entries(),
+        }
+    }
+}
+
+/// a `storage_buffer` binding, `&mut [T]` in the shader needs it to be writable
+///
+/// writable storage is only allowed in compute and fragment shaders
+pub const fn storage_entry(binding: u32, visibility: wgpu::ShaderStages, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+pub const fn uniform_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// a non-arrayed 2d texture, read with `fetch` or `sample` depending on `sample_type`
+pub const fn texture_entry(binding: u32, visibility: wgpu::ShaderStages, sample_type: wgpu::TextureSampleType) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
 }
 
 #[macro_export]
@@ -25,6 +88,10 @@ macro_rules! bind_group_layout {
                     })
                     .clone()
             }
+            fn bind_group_layout_entries() -> &'static [wgpu::BindGroupLayoutEntry] {
+                static ENTRIES: std::sync::OnceLock<&'static [wgpu::BindGroupLayoutEntry]> = std::sync::OnceLock::new();
+                ENTRIES.get_or_init(|| $layout.entries.to_vec().leak())
+            }
         }
     };
 }
@@ -1,7 +1,8 @@
-use buffer_ext::AsyncBufferWriteExt;
+use buffer_ext::{AsyncBufferReadExt, AsyncBufferWriteExt};
 
 pub mod buffer_ext;
 
 pub mod index;
 pub mod storage;
 pub mod uniform;
+pub mod vertex;
@@ -3,18 +3,31 @@ use {
     anyhow::{Context, Result},
     futures::channel::oneshot,
     shader_types::bytemuck::{self, AnyBitPattern, NoUninit},
+    std::iter::once,
     tap::prelude::*,
     tracing::{error, trace},
-    wgpu::{MapMode, WasmNotSend},
+    wgpu::{MapMode, WasmNotSend, COPY_BUFFER_ALIGNMENT},
 };
 
 #[extension_traits::extension(pub(super) trait AsyncBufferWriteExt)]
 impl wgpu::Buffer {
-    async fn write_async<'a, T, F>(&'a self, device: &wgpu::Device, bounds: std::ops::Range<u64>, write: F) -> Result<()>
+    /// maps the buffer when it can be, otherwise `bounds` are read back, written and uploaded through `queue`
+    async fn write_async<'a, T, F>(&'a self, device: &wgpu::Device, queue: &wgpu::Queue, bounds: std::ops::Range<u64>, write: F) -> Result<()>
     where
         T: NoUninit + AnyBitPattern + 'a,
         F: FnOnce(&mut [T]) + WasmNotSend + 'static,
     {
+        if !self.usage().contains(wgpu::BufferUsages::MAP_WRITE) {
+            let offset = bounds.start * (core::mem::size_of::<T>() as u64);
+            return self
+                .read_async::<T>(device, queue, bounds)
+                .await
+                .context("reading what is there before writing over it")
+                .map(|mut data| {
+                    write(&mut data);
+                    queue.write_buffer(self, offset, bytemuck::cast_slice(&data));
+                });
+        }
         let bounds = bounds.map_range(|address| address * (core::mem::size_of::<T>() as u64));
         if bounds.is_empty() {
             trace!("writing to an empty slice [{bounds:?}] is a noop");
@@ -54,3 +67,62 @@ impl wgpu::Buffer {
         rx.await.context("task cancelled")
     }
 }
+
+#[extension_traits::extension(pub(crate) trait AsyncBufferReadExt)]
+impl wgpu::Buffer {
+    /// copies `bounds` into a staging buffer and maps that, so the buffer itself only needs [wgpu::BufferUsages::COPY_SRC]
+    async fn read_async<T>(&self, device: &wgpu::Device, queue: &wgpu::Queue, bounds: std::ops::Range<u64>) -> Result<Vec<T>>
+    where
+        T: NoUninit + AnyBitPattern,
+    {
+        let bounds = bounds.map_range(|address| address * (core::mem::size_of::<T>() as u64));
+        if bounds.is_empty() {
+            trace!("reading from an empty slice [{bounds:?}] is a noop");
+            return Ok(vec![]);
+        }
+        anyhow::ensure!(
+            bounds.end <= self.size(),
+            "reading [{bounds:?}] past the end of a buffer of [{}] bytes",
+            self.size()
+        );
+        // copies have to start and end on a multiple of COPY_BUFFER_ALIGNMENT
+        let aligned = (bounds.start - bounds.start % COPY_BUFFER_ALIGNMENT)
+            ..bounds
+                .end
+                .next_multiple_of(COPY_BUFFER_ALIGNMENT)
+                .min(self.size());
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback staging buffer"),
+            size: aligned.end - aligned.start,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("readback") })
+            .pipe(|mut encoder| {
+                encoder.copy_buffer_to_buffer(self, aligned.start, &staging, 0, staging.size());
+                queue.submit(once(encoder.finish()));
+            });
+
+        let (tx, rx) = oneshot::channel();
+        staging.slice(..).map_async(MapMode::Read, move |mapped| {
+            if tx.send(mapped).is_err() {
+                error!("nobody is waiting for the readback anymore");
+            }
+        });
+        device.poll(wgpu::Maintain::Wait);
+        trace!("waiting for async operation to finish");
+        rx.await.context("task cancelled")?.context("bad read")?;
+
+        let offset = (bounds.start - aligned.start) as usize;
+        let read = staging.slice(..).get_mapped_range().pipe(|mapped| {
+            // the staging buffer is only aligned for copies, so elements are read out one by one rather than cast in place
+            mapped[offset..offset + (bounds.end - bounds.start) as usize]
+                .chunks_exact(core::mem::size_of::<T>())
+                .map(bytemuck::pod_read_unaligned)
+                .collect()
+        });
+        staging.unmap();
+        Ok(read)
+    }
+}
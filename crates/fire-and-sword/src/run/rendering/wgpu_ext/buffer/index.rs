@@ -1,6 +1,6 @@
 use {
-    super::AsyncBufferWriteExt,
-    crate::run::rendering::wgpu_ext::global_context::device,
+    super::{AsyncBufferReadExt, AsyncBufferWriteExt},
+    crate::run::rendering::wgpu_ext::global_context::{device, queue, write_usage},
     anyhow::{Context, Result},
     shader_types::bytemuck::{self},
     tap::prelude::*,
@@ -13,10 +13,18 @@ impl IndexBuffer {
         F: FnOnce(&mut [u32]) + WasmNotSend + 'static,
     {
         self.buffer
-            .write_async(device(), bounds, write)
+            .write_async(device(), queue(), bounds, write)
             .await
             .context("writing to index buffer")
     }
+
+    /// waits for everything submitted so far
+    pub async fn read(&self, bounds: std::ops::Range<u64>) -> Result<Vec<u32>> {
+        self.buffer
+            .read_async(device(), queue(), bounds)
+            .await
+            .context("reading from index buffer")
+    }
 }
 
 pub struct IndexBuffer {
@@ -44,7 +52,7 @@ impl IndexBuffer {
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: struct_label!(),
                 contents: bytemuck::cast_slice(init),
-                usage: wgpu::BufferUsages::INDEX,
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_SRC | write_usage(),
             })
             .pipe(|buffer| Self { len: init.len() as _, buffer })
     }
@@ -1,6 +1,6 @@
 use {
-    super::AsyncBufferWriteExt,
-    crate::run::rendering::wgpu_ext::global_context::device,
+    super::{AsyncBufferReadExt, AsyncBufferWriteExt},
+    crate::run::rendering::wgpu_ext::global_context::{device, queue, write_usage},
     anyhow::{Context, Result},
     shader_types::{
         bytemuck::{self, AnyBitPattern, NoUninit},
@@ -18,10 +18,26 @@ impl<T> StorageBuffer<T> {
         T: NoUninit + AnyBitPattern + 'a,
     {
         self.0
-            .write_async(device(), bounds, write)
+            .write_async(device(), queue(), bounds, write)
             .await
             .with_context(|| format!("writing to buffer of type [{}]", type_name::<T>()))
     }
+
+    /// in elements, fixed when the buffer is created
+    pub fn capacity(&self) -> usize {
+        (self.0.size() / std::mem::size_of::<T>() as u64) as usize
+    }
+
+    /// waits for everything submitted so far, `bounds` are in elements like for [Self::write]
+    pub async fn read(&self, bounds: std::ops::Range<u64>) -> Result<Vec<T>>
+    where
+        T: NoUninit + AnyBitPattern,
+    {
+        self.0
+            .read_async(device(), queue(), bounds)
+            .await
+            .with_context(|| format!("reading from buffer of type [{}]", type_name::<T>()))
+    }
 }
 
 pub struct StorageBuffer<T>(wgpu::Buffer, PhantomData<T>);
@@ -50,7 +66,7 @@ where
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: label!(format!("StorageBuffer<{}>", std::any::type_name::<T>())),
                 contents: bytemuck::cast_slice(init),
-                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::MAP_WRITE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | write_usage(),
             })
             .pipe(|d| Self(d, Default::default()))
     }
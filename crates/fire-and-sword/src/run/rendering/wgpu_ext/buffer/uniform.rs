@@ -1,6 +1,6 @@
 use {
-    super::AsyncBufferWriteExt,
-    crate::run::rendering::wgpu_ext::global_context::device,
+    super::{AsyncBufferReadExt, AsyncBufferWriteExt},
+    crate::run::rendering::wgpu_ext::global_context::{device, queue, write_usage},
     anyhow::{Context, Result},
     shader_types::bytemuck::{self, AnyBitPattern, NoUninit},
     std::{any::type_name, marker::PhantomData},
@@ -15,10 +15,21 @@ impl<T> UniformBuffer<T> {
         T: NoUninit + AnyBitPattern + 'a,
     {
         self.0
-            .write_async(device(), bounds, write)
+            .write_async(device(), queue(), bounds, write)
             .await
             .with_context(|| format!("writing to buffer of type [{}]", type_name::<T>()))
     }
+
+    /// waits for everything submitted so far, `bounds` are in elements like for [Self::write]
+    pub async fn read(&self, bounds: std::ops::Range<u64>) -> Result<Vec<T>>
+    where
+        T: NoUninit + AnyBitPattern,
+    {
+        self.0
+            .read_async(device(), queue(), bounds)
+            .await
+            .with_context(|| format!("reading from buffer of type [{}]", type_name::<T>()))
+    }
 }
 
 pub struct UniformBuffer<T>(wgpu::Buffer, PhantomData<T>);
@@ -38,7 +49,7 @@ where
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: label!(format!("UniformBuffer<{}>", std::any::type_name::<T>())),
                 contents: bytemuck::cast_slice(&[*init]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::MAP_WRITE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_SRC | write_usage(),
             })
             .pipe(|d| Self(d, Default::default()))
     }
@@ -0,0 +1,61 @@
+use {
+    super::AsyncBufferWriteExt,
+    crate::run::rendering::wgpu_ext::global_context::{device, queue, write_usage},
+    anyhow::{Context, Result},
+    shader_types::{
+        bytemuck::{AnyBitPattern, NoUninit},
+        Zeroable,
+    },
+    std::{any::type_name, marker::PhantomData},
+    tap::prelude::*,
+    wgpu::WasmNotSend,
+};
+
+impl<T> VertexBuffer<T> {
+    pub async fn write<'a, F>(&'a self, bounds: std::ops::Range<u64>, write: F) -> Result<()>
+    where
+        F: FnOnce(&mut [T]) + WasmNotSend + 'static,
+        T: NoUninit + AnyBitPattern + 'a,
+    {
+        self.buffer
+            .write_async(device(), queue(), bounds, write)
+            .await
+            .with_context(|| format!("writing to buffer of type [{}]", type_name::<T>()))
+    }
+}
+
+/// for data that changes every frame and is read through vertex attributes
+pub struct VertexBuffer<T> {
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> AsRef<wgpu::Buffer> for VertexBuffer<T> {
+    fn as_ref(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+impl<T> VertexBuffer<T>
+where
+    T: NoUninit + Zeroable,
+{
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn new_empty(capacity: usize) -> Self {
+        device()
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: label!(format!("VertexBuffer<{}>", std::any::type_name::<T>())),
+                size: (capacity * std::mem::size_of::<T>()) as _,
+                usage: wgpu::BufferUsages::VERTEX | write_usage(),
+                mapped_at_creation: false,
+            })
+            .pipe(|buffer| Self {
+                buffer,
+                capacity,
+                _marker: PhantomData,
+            })
+    }
+}
@@ -0,0 +1,25 @@
+use tap::prelude::*;
+
+/// workgroups needed to cover `invocations`, the last one may run past the end so shaders have to bail out themselves
+pub fn workgroups(invocations: u32, workgroup_size: u32) -> u32 {
+    invocations.div_ceil(workgroup_size)
+}
+
+#[extension_traits::extension(pub trait ComputeEncoderExt)]
+impl wgpu::CommandEncoder {
+    /// records a pass running `pipeline`, bind groups and dispatches are up to `with_compute_pass`
+    fn compute_pass<R>(
+        &mut self,
+        label: &str,
+        pipeline: &wgpu::ComputePipeline,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites<'_>>,
+        with_compute_pass: impl FnOnce(&mut wgpu::ComputePass<'_>) -> R,
+    ) -> R {
+        self.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some(label),
+            timestamp_writes,
+        })
+        .tap_mut(|pass| pass.set_pipeline(pipeline))
+        .pipe(|mut pass| with_compute_pass(&mut pass))
+    }
+}
@@ -22,3 +22,14 @@ pub fn init_queue(queue: Queue) {
 pub fn queue<'a>() -> &'a Queue {
     QUEUE.get().expect("queue must be initialized")
 }
+
+/// buffers the shaders read can only be mapped with [wgpu::Features::MAPPABLE_PRIMARY_BUFFERS], otherwise writes go through the queue
+pub fn write_usage() -> wgpu::BufferUsages {
+    match device()
+        .features()
+        .contains(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS)
+    {
+        true => wgpu::BufferUsages::MAP_WRITE,
+        false => wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+    }
+}
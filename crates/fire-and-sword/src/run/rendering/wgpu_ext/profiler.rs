@@ -0,0 +1,199 @@
+use {
+    super::global_context::{device, queue},
+    futures::channel::oneshot,
+    itertools::Itertools,
+    shader_types::bytemuck,
+    std::{collections::VecDeque, time::Duration},
+    tracing::{debug, trace, warn},
+    wgpu::MapMode,
+};
+
+/// passes past this in a single frame go unmeasured
+pub const MAX_PROFILED_PASSES: u32 = 16;
+/// frames whose timestamps can be on their way back at once, frames past this go unmeasured until one arrives
+const MAX_FRAMES_IN_FLIGHT: usize = 3;
+const RESOLVE_SIZE: u64 = (MAX_PROFILED_PASSES * 2) as u64 * wgpu::QUERY_SIZE as u64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PassTiming {
+    pub label: &'static str,
+    pub duration: Duration,
+}
+
+/// the timestamps of a resolved frame, copied out so the queries can be reused right away
+struct InFlight {
+    readback: wgpu::Buffer,
+    passes: Vec<&'static str>,
+    /// [None] until the frame is submitted and the readback is asked to be mapped
+    mapped: Option<oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// times every pass that asks for timestamp writes, the timings show up a few frames later
+///
+/// only exists when the device has [wgpu::Features::TIMESTAMP_QUERY]
+pub struct GpuProfiler {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    /// nanoseconds per tick
+    period: f32,
+    /// passes recorded this frame, each one owns two queries
+    passes: Vec<&'static str>,
+    in_flight: VecDeque<InFlight>,
+    /// readback buffers of frames that have been read, for the next ones
+    spare: Vec<wgpu::Buffer>,
+    timings: Vec<PassTiming>,
+}
+
+impl GpuProfiler {
+    pub fn new() -> Option<Self> {
+        if !device()
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+        {
+            debug!("{:?} is not available, gpu profiling is off", wgpu::Features::TIMESTAMP_QUERY);
+            return None;
+        }
+        Some(Self {
+            query_set: device().create_query_set(&wgpu::QuerySetDescriptor {
+                label: struct_label!(),
+                ty: wgpu::QueryType::Timestamp,
+                count: MAX_PROFILED_PASSES * 2,
+            }),
+            resolve_buffer: device().create_buffer(&wgpu::BufferDescriptor {
+                label: struct_label!(),
+                size: RESOLVE_SIZE,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            period: queue().get_timestamp_period(),
+            passes: Default::default(),
+            in_flight: Default::default(),
+            spare: Default::default(),
+            timings: Default::default(),
+        })
+    }
+
+    /// query indices for the start and end of the next pass, [None] once this frame has used them all up
+    fn next_pass(&mut self, label: &'static str) -> Option<(u32, u32)> {
+        let index = self.passes.len() as u32;
+        (index < MAX_PROFILED_PASSES).then(|| {
+            self.passes.push(label);
+            (index * 2, index * 2 + 1)
+        })
+    }
+
+    pub fn render_pass_writes(&mut self, label: &'static str) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.next_pass(label)
+            .map(|(beginning, end)| wgpu::RenderPassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(beginning),
+                end_of_pass_write_index: Some(end),
+            })
+    }
+
+    pub fn compute_pass_writes(&mut self, label: &'static str) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.next_pass(label)
+            .map(|(beginning, end)| wgpu::ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(beginning),
+                end_of_pass_write_index: Some(end),
+            })
+    }
+
+    /// has to be recorded after the last profiled pass of the frame, into the same encoder
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let passes = std::mem::take(&mut self.passes);
+        if passes.is_empty() {
+            return;
+        }
+        if self.in_flight.len() >= MAX_FRAMES_IN_FLIGHT {
+            trace!("[{}] frames of timestamps are still on their way back, skipping this one", self.in_flight.len());
+            return;
+        }
+        let queries = passes.len() as u32 * 2;
+        encoder.resolve_query_set(&self.query_set, 0..queries, &self.resolve_buffer, 0);
+        let readback = self.spare.pop().unwrap_or_else(|| {
+            device().create_buffer(&wgpu::BufferDescriptor {
+                label: struct_label!(),
+                size: RESOLVE_SIZE,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            })
+        });
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &readback, 0, queries as u64 * wgpu::QUERY_SIZE as u64);
+        self.in_flight.push_back(InFlight {
+            readback,
+            passes,
+            mapped: None,
+        });
+    }
+
+    fn read(&self, InFlight { readback, passes, .. }: &InFlight) -> Vec<PassTiming> {
+        let timings = readback
+            .slice(..)
+            .get_mapped_range()
+            .chunks_exact(std::mem::size_of::<u64>())
+            .map(bytemuck::pod_read_unaligned::<u64>)
+            .tuples()
+            .zip(passes)
+            .map(|((beginning, end), label)| PassTiming {
+                label,
+                duration: Duration::from_nanos((end.saturating_sub(beginning) as f64 * self.period as f64) as u64),
+            })
+            .inspect(|PassTiming { label, duration }| trace!("[{label}] took {duration:?}"))
+            .collect();
+        readback.unmap();
+        timings
+    }
+
+    /// maps whatever was submitted since the last call and takes in the frames that made it back, never waits on the gpu
+    ///
+    /// call before recording the next frame
+    pub fn collect(&mut self) {
+        self.passes.clear();
+        self.in_flight
+            .iter_mut()
+            .filter(|frame| frame.mapped.is_none())
+            .for_each(|frame| {
+                let (tx, rx) = oneshot::channel();
+                frame
+                    .readback
+                    .slice(..)
+                    .map_async(MapMode::Read, move |mapped| {
+                        // nobody listening means the profiler was turned off meanwhile
+                        let _ = tx.send(mapped);
+                    });
+                frame.mapped = Some(rx);
+            });
+        device().poll(wgpu::Maintain::Poll);
+        while let Some(mapped) = self
+            .in_flight
+            .front_mut()
+            .and_then(|frame| frame.mapped.as_mut())
+            .map(|mapped| mapped.try_recv())
+        {
+            match mapped {
+                Ok(None) => break,
+                Ok(Some(Ok(()))) => {
+                    if let Some(frame) = self.in_flight.pop_front() {
+                        self.timings = self.read(&frame);
+                        self.spare.push(frame.readback);
+                    }
+                }
+                Ok(Some(Err(reason))) => {
+                    warn!("gpu timings are unavailable for a frame: {reason}");
+                    self.in_flight.pop_front();
+                }
+                Err(oneshot::Canceled) => {
+                    warn!("gpu timings are unavailable for a frame: mapping was cancelled");
+                    self.in_flight.pop_front();
+                }
+            }
+        }
+    }
+
+    /// every pass of the latest frame that made it back, in the order they were recorded
+    pub fn timings(&self) -> &[PassTiming] {
+        &self.timings
+    }
+}
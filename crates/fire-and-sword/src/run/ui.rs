@@ -0,0 +1,349 @@
+use {
+    super::rendering::{
+        animation::{Animations, Clip},
+        identify::WithId,
+        model::material::LoadedMaterial,
+        scene::NodeData,
+        ui::UiFrame,
+    },
+    crate::{
+        game::GameState,
+        run::rendering::scene::{Node, WithTransform},
+    },
+    egui::{DragValue, Event, Key, PointerButton},
+    shader_types::{material::MaterialFactors, Vec3},
+    std::time::Instant,
+    winit::{
+        dpi::PhysicalSize,
+        event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
+        keyboard::KeyCode,
+    },
+};
+
+const DRAG_SPEED: f32 = 0.05;
+
+/// egui panels for inspecting and editing the [GameState], fed from window events
+#[derive(Default)]
+pub struct Ui {
+    context: egui::Context,
+    events: Vec<Event>,
+    pointer: egui::Pos2,
+    pixels_per_point: f32,
+    /// egui animates and tells double clicks apart by the time since this
+    started: Option<Instant>,
+    pub open: bool,
+}
+
+/// what the panels changed that the renderer has to be told about
+#[derive(Default)]
+pub struct UiChanges {
+    pub light_sources: bool,
+    /// the factors to write for every material that was edited
+    pub materials: Vec<(WithId<LoadedMaterial>, MaterialFactors)>,
+}
+
+fn key(key: KeyCode) -> Option<Key> {
+    match key {
+        KeyCode::ArrowDown => Some(Key::ArrowDown),
+        KeyCode::ArrowLeft => Some(Key::ArrowLeft),
+        KeyCode::ArrowRight => Some(Key::ArrowRight),
+        KeyCode::ArrowUp => Some(Key::ArrowUp),
+        KeyCode::Escape => Some(Key::Escape),
+        KeyCode::Tab => Some(Key::Tab),
+        KeyCode::Backspace => Some(Key::Backspace),
+        KeyCode::Enter => Some(Key::Enter),
+        KeyCode::Delete => Some(Key::Delete),
+        KeyCode::Home => Some(Key::Home),
+        KeyCode::End => Some(Key::End),
+        _ => None,
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        _ => None,
+    }
+}
+
+fn drag_vec3(ui: &mut egui::Ui, value: &mut Vec3) -> bool {
+    ui.horizontal(|ui| {
+        [("x: ", &mut value.x), ("y: ", &mut value.y), ("z: ", &mut value.z)]
+            .into_iter()
+            .fold(false, |changed, (prefix, value)| {
+                ui.add(DragValue::new(value).speed(DRAG_SPEED).prefix(prefix))
+                    .changed()
+                    | changed
+            })
+    })
+    .inner
+}
+
+fn clip_name(clips: &[Clip], idx: usize) -> String {
+    clips[idx]
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("clip #{idx}"))
+}
+
+/// seeking while paused shows up on the next tick, that is when nodes get posed
+fn animation_ui(ui: &mut egui::Ui, Animations { clips, playback }: &mut Animations) {
+    egui::ComboBox::from_label("clip")
+        .selected_text(clip_name(clips, playback.clip))
+        .show_ui(ui, |ui| {
+            (0..clips.len()).for_each(|idx| {
+                if ui
+                    .selectable_value(&mut playback.clip, idx, clip_name(clips, idx))
+                    .changed()
+                {
+                    playback.time = 0.;
+                }
+            })
+        });
+    let duration = clips[playback.clip].duration;
+    ui.horizontal(|ui| {
+        match playback.playing {
+            true if ui.button("pause").clicked() => playback.playing = false,
+            false if ui.button("play").clicked() => playback.play(duration),
+            _ => {}
+        }
+        ui.checkbox(&mut playback.looping, "loop");
+        ui.add(
+            DragValue::new(&mut playback.speed)
+                .speed(DRAG_SPEED)
+                .prefix("speed: "),
+        );
+    });
+    let mut time = playback.time;
+    if ui
+        .add(egui::Slider::new(&mut time, 0. ..=duration).suffix(" s"))
+        .changed()
+    {
+        playback.seek(time, duration);
+    }
+}
+
+fn node_ui(
+    ui: &mut egui::Ui,
+    changes: &mut UiChanges,
+    idx: usize,
+    WithTransform {
+        inner: Node { data, children, .. },
+        transform,
+    }: &mut WithTransform<Node>,
+) {
+    ui.collapsing(format!("node #{idx}"), |ui| {
+        let mut translation = Vec3::from(transform.translation);
+        if drag_vec3(ui, &mut translation) {
+            transform.translation = translation.into();
+        }
+        match data {
+            Some(NodeData::Camera(_)) => {
+                ui.label("camera");
+            }
+            Some(NodeData::Model(model)) => {
+                ui.label(format!("model, bounds {:.2} to {:.2}", model.bounds.min, model.bounds.max));
+                ui.label(format!("level of detail [{}] of [{}]", model.lod_selection.current(), model.lods.len()));
+                model
+                    .primitives
+                    .iter()
+                    .enumerate()
+                    .for_each(|(idx, primitive)| {
+                        let material = primitive.material.as_ref();
+                        ui.label(format!("primitive #{idx}: {:?}", material.features));
+                        // shared with every other primitive using the material
+                        let mut factors = material.factors.get();
+                        ui.horizontal(|ui| {
+                            ui.label("base color");
+                            if ui
+                                .color_edit_button_rgba_unmultiplied(factors.base_color.as_mut())
+                                .changed()
+                            {
+                                changes
+                                    .materials
+                                    .push((primitive.material.clone(), factors));
+                            }
+                        });
+                    });
+            }
+            Some(NodeData::Emitter(emitter)) => {
+                ui.label(format!(
+                    "particle emitter, {:?} blending, up to [{}] particles",
+                    emitter.params.blend, emitter.params.max_particles
+                ));
+                ui.add(
+                    DragValue::new(&mut emitter.params.rate)
+                        .range(0. ..=f32::MAX)
+                        .prefix("rate: "),
+                );
+            }
+            None => {}
+        }
+        children.iter_mut().enumerate().for_each(|(idx, child)| {
+            ui.push_id(idx, |ui| node_ui(ui, changes, idx, child));
+        });
+    });
+}
+
+impl Ui {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.events.clear();
+    }
+
+    /// true when camera controls should leave the keyboard alone
+    pub fn wants_keyboard(&self) -> bool {
+        self.open && self.context.wants_keyboard_input()
+    }
+
+    pub fn push_window_event(&mut self, event: &WindowEvent, scale_factor: f64) {
+        if !self.open {
+            return;
+        }
+        self.pixels_per_point = scale_factor as f32;
+        let to_points = |x: f64, y: f64| egui::pos2(x as f32 / self.pixels_per_point, y as f32 / self.pixels_per_point);
+        match event {
+            WindowEvent::PointerMoved { position, .. } => {
+                self.pointer = to_points(position.x, position.y);
+                self.events.push(Event::PointerMoved(self.pointer));
+            }
+            WindowEvent::PointerLeft { .. } => self.events.push(Event::PointerGone),
+            WindowEvent::PointerButton { state, button, .. } => {
+                if let Some(button) = pointer_button(button.mouse_button()) {
+                    self.events.push(Event::PointerButton {
+                        pos: self.pointer,
+                        button,
+                        pressed: state.is_pressed(),
+                        modifiers: Default::default(),
+                    })
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => self.events.push(match delta {
+                MouseScrollDelta::LineDelta(x, y) => Event::MouseWheel {
+                    unit: egui::MouseWheelUnit::Line,
+                    delta: egui::vec2(*x, *y),
+                    modifiers: Default::default(),
+                },
+                MouseScrollDelta::PixelDelta(delta) => Event::MouseWheel {
+                    unit: egui::MouseWheelUnit::Point,
+                    delta: egui::vec2(delta.x as f32 / self.pixels_per_point, delta.y as f32 / self.pixels_per_point),
+                    modifiers: Default::default(),
+                },
+            }),
+            _ => {}
+        }
+    }
+
+    pub fn push_key(&mut self, code: KeyCode, state: ElementState, text: Option<String>) {
+        if !self.open {
+            return;
+        }
+        if let Some(key) = key(code) {
+            self.events.push(Event::Key {
+                key,
+                physical_key: Some(key),
+                pressed: state.is_pressed(),
+                repeat: false,
+                modifiers: Default::default(),
+            });
+        }
+        if let Some(text) = text.filter(|text| state.is_pressed() && !text.chars().any(char::is_control)) {
+            self.events.push(Event::Text(text));
+        }
+    }
+
+    /// lays out the panels, or returns [None] while the ui is closed
+    pub fn run(&mut self, game_state: &mut GameState, PhysicalSize { width, height }: PhysicalSize<u32>, scale_factor: f64) -> Option<(UiFrame, UiChanges)> {
+        if !self.open {
+            return None;
+        }
+        self.pixels_per_point = scale_factor as f32;
+        let pixels_per_point = self.pixels_per_point;
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                Default::default(),
+                egui::vec2(width as f32 / pixels_per_point, height as f32 / pixels_per_point),
+            )),
+            // without it egui tessellates at 1 pixel per point while laying out in scaled points
+            viewports: std::iter::once((
+                egui::ViewportId::ROOT,
+                egui::ViewportInfo {
+                    native_pixels_per_point: Some(pixels_per_point),
+                    focused: Some(true),
+                    ..Default::default()
+                },
+            ))
+            .collect(),
+            time: Some(
+                self.started
+                    .get_or_insert_with(Instant::now)
+                    .elapsed()
+                    .as_secs_f64(),
+            ),
+            events: std::mem::take(&mut self.events),
+            focused: true,
+            ..Default::default()
+        };
+        let mut changes = UiChanges::default();
+        let GameState {
+            camera, scene, light_sources, ..
+        } = game_state;
+        let output = self.context.run(input, |context| {
+            egui::Window::new("camera").show(context, |ui| {
+                let mut position = camera.position();
+                if drag_vec3(ui, &mut position) {
+                    camera.position_mut(|current| *current = position);
+                }
+                ui.horizontal(|ui| {
+                    camera.rotation_mut(|yaw, pitch| {
+                        ui.add(DragValue::new(yaw).speed(DRAG_SPEED).prefix("yaw: "));
+                        ui.add(DragValue::new(pitch).speed(DRAG_SPEED).prefix("pitch: "));
+                    })
+                });
+            });
+            egui::Window::new("light sources").show(context, |ui| {
+                light_sources
+                    .iter_mut()
+                    .enumerate()
+                    .for_each(|(idx, light)| {
+                        ui.push_id(idx, |ui| {
+                            ui.label(format!("light #{idx}"));
+                            let mut position = light.position.truncate();
+                            if drag_vec3(ui, &mut position) {
+                                light.position = position.extend(1.);
+                                changes.light_sources = true;
+                            }
+                            changes.light_sources |= ui
+                                .color_edit_button_rgba_unmultiplied(&mut light.color.0)
+                                .changed();
+                        });
+                    });
+            });
+            egui::Window::new("scene")
+                .default_open(false)
+                .show(context, |ui| match scene {
+                    Some(scene) => scene.nodes.iter_mut().enumerate().for_each(|(idx, node)| {
+                        ui.push_id(idx, |ui| node_ui(ui, &mut changes, idx, node));
+                    }),
+                    None => {
+                        ui.label("no scene loaded");
+                    }
+                });
+            if let Some(animations) = scene.as_mut().and_then(|scene| scene.animations.as_mut()) {
+                egui::Window::new("animation").show(context, |ui| animation_ui(ui, animations));
+            }
+        });
+        Some((
+            UiFrame {
+                primitives: self
+                    .context
+                    .tessellate(output.shapes, output.pixels_per_point),
+                textures_delta: output.textures_delta,
+                pixels_per_point: output.pixels_per_point,
+            },
+            changes,
+        ))
+    }
+}
@@ -0,0 +1,90 @@
+use {
+    super::config::FILE_WATCH_INTERVAL,
+    futures::Stream,
+    std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tokio::sync::watch,
+    tracing::debug,
+};
+
+async fn modified_at(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// yields the path every time the file is modified on disk
+///
+/// a change is only reported once the modification time stays the same for a full
+/// [FILE_WATCH_INTERVAL], so files that are still being written are not picked up
+pub fn watch_file(path: PathBuf) -> impl Stream<Item = PathBuf> {
+    futures::stream::unfold((path, None), |(path, seen)| async move {
+        let mut seen = match seen {
+            Some(seen) => seen,
+            None => modified_at(&path).await,
+        };
+        let mut changed = false;
+        loop {
+            tokio::time::sleep(FILE_WATCH_INTERVAL).await;
+            match modified_at(&path).await {
+                modified if modified != seen => {
+                    changed = modified.is_some();
+                    seen = modified;
+                }
+                _ if changed => {
+                    debug!("[{}] changed on disk", path.display());
+                    break Some((path.clone(), (path, Some(seen))));
+                }
+                _ => {}
+            }
+        }
+    })
+}
+
+/// the set is copied out first, holding on to it across an await would block whoever updates it
+async fn modified_all(paths: &watch::Receiver<Vec<PathBuf>>) -> HashMap<PathBuf, Option<SystemTime>> {
+    let paths = paths.borrow().clone();
+    futures::future::join_all(
+        paths
+            .iter()
+            .map(|path| async move { (path.clone(), modified_at(path).await) }),
+    )
+    .await
+    .into_iter()
+    .collect()
+}
+
+/// [watch_file] for a set of files that may grow or shrink between changes, yields the first of them that changed
+///
+/// files joining the set are only compared from the moment they join, so swapping the set doesn't count as a change
+pub fn watch_files(paths: watch::Receiver<Vec<PathBuf>>) -> impl Stream<Item = PathBuf> {
+    futures::stream::unfold((paths, None), |(paths, seen)| async move {
+        let mut seen = match seen {
+            Some(seen) => seen,
+            None => modified_all(&paths).await,
+        };
+        let mut changed = None;
+        loop {
+            tokio::time::sleep(FILE_WATCH_INTERVAL).await;
+            let modified = modified_all(&paths).await;
+            let moved = modified
+                .iter()
+                .find(|(path, modified)| seen.get(*path).is_some_and(|seen| seen != *modified))
+                .map(|(path, modified)| (path.clone(), modified.is_some()));
+            seen = modified;
+            match moved {
+                Some((path, exists)) => changed = exists.then_some(path),
+                None => {
+                    if let Some(path) = changed.take() {
+                        debug!("[{}] changed on disk", path.display());
+                        break Some((path, (paths, Some(seen))));
+                    }
+                }
+            }
+        }
+    })
+}
@@ -21,6 +21,177 @@ pub struct Instance {
 
 pub mod model;
 
+pub mod camera {
+    pub const Z_NEAR: f32 = 0.1;
+    pub const Z_FAR: f32 = 100.;
+}
+
+pub mod gizmo {
+    use {
+        crate::Color,
+        bytemuck::{Pod, Zeroable},
+        glam::Vec4,
+    };
+
+    /// one end of a debug line, read as a vertex attribute rather than pulled from storage
+    #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct GizmoVertex {
+        pub position: Vec4,
+        pub color: Color,
+    }
+}
+
+pub mod text {
+    use {
+        crate::Color,
+        bytemuck::{Pod, Zeroable},
+        glam::Vec4,
+    };
+
+    /// one quad of text, expanded from six vertices of the same instance
+    #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct GlyphInstance {
+        /// left, top, right, bottom in clip space
+        pub rect: Vec4,
+        /// left, top, right, bottom in the glyph atlas
+        pub tex_coords: Vec4,
+        pub color: Color,
+    }
+}
+
+pub mod particle {
+    use {
+        bytemuck::{Pod, Zeroable},
+        glam::Vec4,
+    };
+
+    /// threads per workgroup of `particles_cs`
+    pub const PARTICLE_WORKGROUP_SIZE: u32 = 64;
+
+    /// one simulated particle, dead once its age reaches its lifetime
+    #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct Particle {
+        /// w is the age, in seconds
+        pub position: Vec4,
+        /// w is the lifetime, in seconds
+        pub velocity: Vec4,
+    }
+
+    /// everything a single emitter needs for one step of the simulation and for drawing its particles
+    #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct Emitter {
+        /// w is how far from it particles spawn
+        pub origin: Vec4,
+        /// w is how much of it is randomized in every direction
+        pub velocity: Vec4,
+        /// w is the time step, in seconds
+        pub acceleration: Vec4,
+        pub color_start: Vec4,
+        pub color_end: Vec4,
+        /// camera right and up, billboards are spanned by them
+        pub billboard_right: Vec4,
+        pub billboard_up: Vec4,
+        pub size_start: f32,
+        pub size_end: f32,
+        pub lifetime: f32,
+        pub padding: f32,
+        /// particles from `spawn_start` on, wrapping around `capacity`, are respawned this step
+        pub spawn_start: u32,
+        pub spawn_count: u32,
+        pub seed: u32,
+        pub capacity: u32,
+    }
+}
+
+pub mod material {
+    use {
+        bytemuck::{Pod, Zeroable},
+        glam::Vec4,
+    };
+
+    /// per material constants, multiplied with whatever its textures hold
+    #[derive(Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct MaterialFactors {
+        pub base_color: Vec4,
+        /// x scales the specular highlight, yzw unused
+        pub specular: Vec4,
+    }
+
+    impl Default for MaterialFactors {
+        fn default() -> Self {
+            Self {
+                base_color: Vec4::ONE,
+                specular: Vec4::X,
+            }
+        }
+    }
+}
+
+pub mod debug_view {
+    use {
+        bytemuck::{Pod, Zeroable},
+        glam::UVec4,
+    };
+
+    #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct DebugViewOptions {
+        /// x indexes into the light sources shown by `debug_light_fs`, yzw unused
+        pub light_index: UVec4,
+    }
+}
+
+pub mod deferred {
+    use {
+        bytemuck::{Pod, Zeroable},
+        glam::{Mat4, Vec4},
+    };
+
+    /// what the lighting pass needs to get from a g-buffer texel back to world space
+    #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct GBufferView {
+        pub inverse_view_projection: Mat4,
+        /// in pixels, zw unused
+        pub screen_size: Vec4,
+    }
+}
+
+pub mod cluster {
+    use {
+        bytemuck::{Pod, Zeroable},
+        glam::{Mat4, Vec4},
+    };
+
+    /// screen tiles across and down
+    pub const CLUSTERS_X: u32 = 16;
+    pub const CLUSTERS_Y: u32 = 9;
+    /// depth slices between [crate::camera::Z_NEAR] and [crate::camera::Z_FAR], thicker the further away they are
+    pub const CLUSTERS_Z: u32 = 24;
+    pub const CLUSTER_COUNT: u32 = CLUSTERS_X * CLUSTERS_Y * CLUSTERS_Z;
+    /// lights past this in a single cluster are left out
+    pub const MAX_LIGHTS_PER_CLUSTER: u32 = 32;
+    /// words per cluster, the light count followed by up to [MAX_LIGHTS_PER_CLUSTER] light indices
+    pub const CLUSTER_STRIDE: u32 = MAX_LIGHTS_PER_CLUSTER + 1;
+    /// threads per workgroup of `cluster_lights_cs`, one per cluster
+    pub const CLUSTER_WORKGROUP_SIZE: u32 = 64;
+
+    /// what both the light assignment and `main_fs` need to agree on which cluster a point falls into
+    #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
+    #[repr(C)]
+    pub struct ClusterView {
+        pub view: Mat4,
+        pub inverse_projection: Mat4,
+        /// in pixels, zw unused
+        pub screen_size: Vec4,
+    }
+}
+
 pub mod light_source {
     use {
         crate::Color,
@@ -28,6 +199,9 @@ pub mod light_source {
         glam::Vec4,
     };
 
+    /// lights don't reach surfaces further away than this, squared
+    pub const LIGHT_RANGE_SQUARED: f32 = 2000.;
+
     #[derive(Default, Clone, Copy, Debug, Pod, Zeroable)]
     #[repr(C)]
     pub struct LightSource {
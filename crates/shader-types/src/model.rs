@@ -11,4 +11,8 @@ pub struct ModelVertex {
     pub normal: Vec4,
     pub tex_coords: Vec2,
     pub padding: WithPadding<2, ()>,
+    /// indices into the joint matrices of the skin, stored as floats so the vertex can still be handed to the fragment stage
+    pub joints: Vec4,
+    /// zero for vertices that aren't skinned
+    pub weights: Vec4,
 }
@@ -0,0 +1,93 @@
+#[cfg(target_arch = "spirv")]
+#[allow(unused_imports)]
+use spirv_std::num_traits::Float;
+use {
+    crate::compute::invocation_index,
+    glam::{Mat4, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles},
+    shader_types::{
+        camera::{Z_FAR, Z_NEAR},
+        cluster::{ClusterView, CLUSTERS_X, CLUSTERS_Y, CLUSTERS_Z, CLUSTER_COUNT, CLUSTER_STRIDE, MAX_LIGHTS_PER_CLUSTER},
+        light_source::{LightSource, LIGHT_RANGE_SQUARED},
+    },
+    spirv_std::spirv,
+};
+
+/// view space depth, positive in front of the camera, where depth slice `slice` starts
+fn slice_depth(slice: u32) -> f32 {
+    Z_NEAR * (Z_FAR / Z_NEAR).powf(slice as f32 / CLUSTERS_Z as f32)
+}
+
+/// inverse of [slice_depth]
+fn depth_slice(depth: f32) -> u32 {
+    ((depth / Z_NEAR).ln() / (Z_FAR / Z_NEAR).ln() * CLUSTERS_Z as f32)
+        .floor()
+        .clamp(0., (CLUSTERS_Z - 1) as f32) as u32
+}
+
+/// the point `depth` in front of the camera on the ray through `ndc`
+fn view_point(inverse_projection: &Mat4, ndc: Vec2, depth: f32) -> Vec3 {
+    let far = *inverse_projection * ndc.extend(1.).extend(1.);
+    let direction = far.xyz() / far.w;
+    direction * (depth / -direction.z)
+}
+
+/// the word in the cluster buffer where the cluster holding `frag_coord` starts, `position` is in world space
+pub fn cluster_offset(frag_coord: Vec4, position: Vec3, view: &ClusterView) -> usize {
+    let tile = (frag_coord.xy() / view.screen_size.xy() * Vec2::new(CLUSTERS_X as f32, CLUSTERS_Y as f32))
+        .floor()
+        .clamp(Vec2::ZERO, Vec2::new((CLUSTERS_X - 1) as f32, (CLUSTERS_Y - 1) as f32));
+    let slice = depth_slice(-view.view.transform_point3(position).z);
+    ((slice * CLUSTERS_Y + tile.y as u32) * CLUSTERS_X + tile.x as u32) as usize * CLUSTER_STRIDE as usize
+}
+
+/// one invocation per cluster, collects every light whose range overlaps the bounds of the cluster in view space
+#[spirv(compute(threads(64)))]
+pub fn cluster_lights_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] light_sources: &[LightSource],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] clusters: &mut [u32],
+    #[spirv(uniform, descriptor_set = 0, binding = 2)] view: &ClusterView,
+) {
+    let Some(cluster) = invocation_index(id, CLUSTER_COUNT) else {
+        return;
+    };
+    let x = cluster as u32 % CLUSTERS_X;
+    let y = cluster as u32 / CLUSTERS_X % CLUSTERS_Y;
+    let z = cluster as u32 / (CLUSTERS_X * CLUSTERS_Y);
+    // tiles count rows from the top of the screen, ndc from the bottom
+    let ndc_min = Vec2::new(x as f32 / CLUSTERS_X as f32 * 2. - 1., 1. - (y + 1) as f32 / CLUSTERS_Y as f32 * 2.);
+    let ndc_max = Vec2::new((x + 1) as f32 / CLUSTERS_X as f32 * 2. - 1., 1. - y as f32 / CLUSTERS_Y as f32 * 2.);
+    let (near, far) = (slice_depth(z), slice_depth(z + 1));
+
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
+    let mut corner = 0;
+    while corner < 8 {
+        let ndc = Vec2::new(
+            if corner & 1 == 0 { ndc_min.x } else { ndc_max.x },
+            if corner & 2 == 0 { ndc_min.y } else { ndc_max.y },
+        );
+        let point = view_point(&view.inverse_projection, ndc, if corner & 4 == 0 { near } else { far });
+        min = min.min(point);
+        max = max.max(point);
+        corner += 1;
+    }
+
+    let offset = cluster * CLUSTER_STRIDE as usize;
+    let mut count = 0;
+    let mut idx = 0;
+    loop {
+        if idx == light_sources.len() || count == MAX_LIGHTS_PER_CLUSTER {
+            break;
+        }
+        let center = view
+            .view
+            .transform_point3(light_sources[idx].position.xyz());
+        if center.clamp(min, max).distance_squared(center) <= LIGHT_RANGE_SQUARED {
+            clusters[offset + 1 + count as usize] = idx as u32;
+            count += 1;
+        }
+        idx += 1;
+    }
+    clusters[offset] = count;
+}
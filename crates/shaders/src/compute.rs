@@ -0,0 +1,22 @@
+use glam::{UVec3, Vec3};
+
+/// index of this invocation into a buffer of `len` elements, [None] for the threads past the end of the last workgroup
+pub fn invocation_index(global_invocation_id: UVec3, len: u32) -> Option<usize> {
+    (global_invocation_id.x < len).then_some(global_invocation_id.x as usize)
+}
+
+/// pcg, good enough to scatter things without keeping any state on the gpu
+pub fn hash(input: u32) -> u32 {
+    let state = input.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
+
+/// each component somewhere between -1 and 1, advances `seed`
+pub fn random_vec3(seed: &mut u32) -> Vec3 {
+    let mut next = || {
+        *seed = hash(*seed);
+        (*seed as f32 / u32::MAX as f32) * 2. - 1.
+    };
+    Vec3::new(next(), next(), next())
+}
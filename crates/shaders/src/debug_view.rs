@@ -0,0 +1,67 @@
+#[cfg(target_arch = "spirv")]
+#[allow(unused_imports)]
+use spirv_std::num_traits::Float;
+use {
+    crate::{cluster::cluster_offset, lighting::LightContext},
+    glam::{Mat4, Vec3, Vec4, Vec4Swizzles},
+    shader_types::{
+        camera::{Z_FAR, Z_NEAR},
+        cluster::{ClusterView, MAX_LIGHTS_PER_CLUSTER},
+        debug_view::DebugViewOptions,
+        light_source::LightSource,
+        model::ModelVertex,
+    },
+    spirv_std::spirv,
+};
+
+#[spirv(fragment)]
+pub fn debug_normals_fs(model_vertex: ModelVertex, output: &mut Vec4) {
+    *output = (model_vertex.normal.xyz().normalize_or_zero() * 0.5 + 0.5).extend(1.);
+}
+
+#[spirv(fragment)]
+pub fn debug_tex_coords_fs(model_vertex: ModelVertex, output: &mut Vec4) {
+    *output = model_vertex.tex_coords.fract().extend(0.).extend(1.);
+}
+
+/// near is white, far is black
+#[spirv(fragment)]
+pub fn debug_depth_fs(#[spirv(frag_coord)] frag_coord: Vec4, output: &mut Vec4) {
+    let linear = Z_NEAR * Z_FAR / (Z_FAR - frag_coord.z * (Z_FAR - Z_NEAR));
+    *output = Vec3::splat(1. - (linear - Z_NEAR) / (Z_FAR - Z_NEAR)).extend(1.);
+}
+
+/// how many lights reach the cluster of each fragment, black for none, green through red up to a full cluster
+#[spirv(fragment)]
+pub fn debug_clusters_fs(
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(storage_buffer, descriptor_set = 4, binding = 1)] clusters: &[u32],
+    #[spirv(uniform, descriptor_set = 4, binding = 2)] cluster_view: &ClusterView,
+    model_vertex: ModelVertex,
+    output: &mut Vec4,
+) {
+    let count = clusters[cluster_offset(frag_coord, model_vertex.position.xyz(), cluster_view)];
+    let heat = count as f32 / MAX_LIGHTS_PER_CLUSTER as f32;
+    *output = match count {
+        0 => Vec3::ZERO,
+        _ => Vec3::new(heat, 1. - heat, 0.),
+    }
+    .extend(1.);
+}
+
+/// lighting from a single light source, without the texture
+#[spirv(fragment)]
+pub fn debug_light_fs(
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] camera: &Mat4,
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] options: &DebugViewOptions,
+    #[spirv(storage_buffer, descriptor_set = 4, binding = 0)] light_sources: &[LightSource],
+    model_vertex: ModelVertex,
+    output: &mut Vec4,
+) {
+    let mut lighting = Vec3::new(0., 0., 0.);
+    let idx = options.light_index.x as usize;
+    if idx < light_sources.len() {
+        LightContext::new(model_vertex, light_sources[idx], camera).apply_light(&mut lighting);
+    }
+    *output = lighting.extend(1.);
+}
@@ -0,0 +1,111 @@
+#[cfg(target_arch = "spirv")]
+#[allow(unused_imports)]
+use spirv_std::num_traits::Float;
+use {
+    crate::{
+        lighting::LightContext,
+        text::{CORNER_X, CORNER_Y},
+    },
+    glam::{Mat4, Vec2, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles},
+    shader_types::{
+        deferred::GBufferView,
+        light_source::{LightSource, LIGHT_RANGE_SQUARED},
+        material::MaterialFactors,
+        model::ModelVertex,
+    },
+    spirv_std::{image::Image2d, spirv, Image, Sampler},
+};
+
+/// opaque surfaces in deferred mode, the surface itself is cleared to black so light volumes can add up on top of it
+#[spirv(fragment)]
+pub fn gbuffer_fs(
+    #[spirv(descriptor_set = 2, binding = 0)] image: &Image2d,
+    #[spirv(descriptor_set = 2, binding = 1)] sampler: &Sampler,
+    #[spirv(uniform, descriptor_set = 2, binding = 2)] material: &MaterialFactors,
+    model_vertex: ModelVertex,
+    output: &mut Vec4,
+    out_albedo: &mut Vec4,
+    out_normal: &mut Vec4,
+    out_material: &mut Vec4,
+) {
+    *output = Vec4::new(0., 0., 0., 1.);
+    let albedo: Vec4 = image.sample(*sampler, model_vertex.tex_coords);
+    *out_albedo = albedo * material.base_color;
+    *out_normal = model_vertex.normal.xyz().normalize_or_zero().extend(0.);
+    *out_material = Vec4::new(material.specular.x, 0., 0., 1.);
+}
+
+/// a cube around the light, big enough to hold its range
+///
+/// it is wound inside out, so back face culling keeps the far side and the volume still covers the screen with the camera inside it
+#[spirv(vertex)]
+pub fn light_volume_vs(
+    #[spirv(vertex_index)] vertex_index: u32,
+    #[spirv(instance_index)] instance_index: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] camera: &Mat4,
+    #[spirv(storage_buffer, descriptor_set = 2, binding = 0)] light_sources: &[LightSource],
+    #[spirv(position)] out_pos: &mut Vec4,
+    #[spirv(flat)] out_light_index: &mut u32,
+) {
+    // six vertices per face, faces go -x, +x, -y, +y, -z, +z
+    let face = vertex_index / 6;
+    let corner = vertex_index % 6;
+    let u = ((CORNER_X >> corner) & 1) as f32 * 2. - 1.;
+    let v = ((CORNER_Y >> corner) & 1) as f32 * 2. - 1.;
+    let side = (face % 2) as f32 * 2. - 1.;
+    let local = match face % 2 {
+        0 => Vec3::new(side, u, v),
+        _ => Vec3::new(side, v, u),
+    };
+    let offset = match face / 2 {
+        0 => local,
+        1 => local.zxy(),
+        _ => local.yzx(),
+    };
+    let light_source = light_sources[instance_index as usize];
+    *out_pos = *camera * (light_source.position.xyz() + offset * LIGHT_RANGE_SQUARED.sqrt()).extend(1.);
+    *out_light_index = instance_index;
+}
+
+/// adds a single light to every g-buffer texel its volume covers, anything out of range or not drawn at all stays as is
+#[spirv(fragment)]
+pub fn light_volume_fs(
+    #[spirv(frag_coord)] frag_coord: Vec4,
+    #[spirv(flat)] light_index: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] camera: &Mat4,
+    #[spirv(descriptor_set = 1, binding = 0)] albedo: &Image2d,
+    #[spirv(descriptor_set = 1, binding = 1)] normal: &Image2d,
+    #[spirv(descriptor_set = 1, binding = 2)] material: &Image2d,
+    #[spirv(descriptor_set = 1, binding = 3)] depth: &Image!(2D, type=f32, sampled, depth),
+    #[spirv(uniform, descriptor_set = 1, binding = 4)] view: &GBufferView,
+    #[spirv(storage_buffer, descriptor_set = 2, binding = 0)] light_sources: &[LightSource],
+    output: &mut Vec4,
+) {
+    let texel = frag_coord.xy().as_ivec2();
+    let depth: Vec4 = depth.fetch(texel);
+    let depth = depth.x;
+    let light_source = light_sources[light_index as usize];
+    let ndc = Vec2::new(frag_coord.x / view.screen_size.x * 2. - 1., 1. - frag_coord.y / view.screen_size.y * 2.);
+    let world = view.inverse_view_projection * ndc.extend(depth).extend(1.);
+    let position = world.xyz() / world.w;
+    if depth >= 1. || position.distance_squared(light_source.position.xyz()) > LIGHT_RANGE_SQUARED {
+        *output = Vec4::ZERO;
+        return;
+    }
+    let normal: Vec4 = normal.fetch(texel);
+    let material: Vec4 = material.fetch(texel);
+    let albedo: Vec4 = albedo.fetch(texel);
+    let model_vertex = ModelVertex {
+        position: position.extend(1.),
+        normal: normal.xyz().extend(0.),
+        tex_coords: Vec2::ZERO,
+        padding: Default::default(),
+        joints: Vec4::ZERO,
+        weights: Vec4::ZERO,
+    };
+    let mut lighting = Vec3::new(0., 0., 0.);
+    LightContext::new(model_vertex, light_source, camera)
+        .with_specular(material.x)
+        .apply_light(&mut lighting);
+    *output = (albedo.xyz() * lighting).extend(1.);
+}
@@ -0,0 +1,21 @@
+use {
+    glam::{Mat4, Vec4},
+    spirv_std::spirv,
+};
+
+#[spirv(vertex)]
+pub fn gizmo_vs(
+    position: Vec4,
+    color: Vec4,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] camera: &Mat4,
+    #[spirv(position)] out_pos: &mut Vec4,
+    out_color: &mut Vec4,
+) {
+    *out_pos = *camera * position;
+    *out_color = color;
+}
+
+#[spirv(fragment)]
+pub fn gizmo_fs(color: Vec4, output: &mut Vec4) {
+    *output = color;
+}
@@ -6,40 +6,62 @@
 use {
     glam::{Affine3A, Mat4, Vec3, Vec4Swizzles},
     lighting::LightContext,
-    shader_types::{light_source::LightSource, model::ModelVertex, Instance},
+    shader_types::{
+        cluster::ClusterView,
+        light_source::{LightSource, LIGHT_RANGE_SQUARED},
+        material::MaterialFactors,
+        model::ModelVertex,
+        Instance,
+    },
     spirv_std::{glam::Vec4, image::Image2d, spirv, Sampler},
 };
 
+pub mod cluster;
+pub mod compute;
+pub mod debug_view;
+pub mod deferred;
+pub mod gizmo;
 pub mod lighting;
+pub mod particles;
+pub mod text;
 
+/// only goes through the lights `cluster_lights_cs` assigned to the cluster this fragment falls into
 #[spirv(fragment)]
 pub fn main_fs(
+    #[spirv(frag_coord)] frag_coord: Vec4,
     #[spirv(uniform, descriptor_set = 0, binding = 0)] camera: &Mat4,
     #[spirv(descriptor_set = 2, binding = 0)] image: &Image2d,
     #[spirv(descriptor_set = 2, binding = 1)] sampler: &Sampler,
+    #[spirv(uniform, descriptor_set = 2, binding = 2)] material: &MaterialFactors,
     #[spirv(storage_buffer, descriptor_set = 4, binding = 0)] light_sources: &[LightSource],
+    #[spirv(storage_buffer, descriptor_set = 4, binding = 1)] clusters: &[u32],
+    #[spirv(uniform, descriptor_set = 4, binding = 2)] cluster_view: &ClusterView,
     model_vertex: ModelVertex,
     output: &mut Vec4,
 ) {
-    let image_color = image.sample(*sampler, model_vertex.tex_coords);
+    let image_color: Vec4 = image.sample(*sampler, model_vertex.tex_coords);
+    let image_color = image_color * material.base_color;
     {
         let mut lighting = Vec3::new(0., 0., 0.);
+        let offset = cluster::cluster_offset(frag_coord, model_vertex.position.xyz(), cluster_view);
+        let count = clusters[offset] as usize;
 
         // no iterators, need to use loop
         let mut idx = 0;
         loop {
-            if idx == light_sources.len() {
+            if idx == count {
                 break;
             }
-            let light_source = light_sources[idx];
+            let light_source = light_sources[clusters[offset + 1 + idx] as usize];
             if model_vertex
                 .position
                 .xyz()
                 .distance_squared(light_source.position.xyz())
-                <= 2000.
+                <= LIGHT_RANGE_SQUARED
             {
-                let light_context = LightContext::new(model_vertex, light_source, camera);
-                light_context.apply_light(&mut lighting);
+                LightContext::new(model_vertex, light_source, camera)
+                    .with_specular(material.specular.x)
+                    .apply_light(&mut lighting);
             }
 
             idx += 1;
@@ -55,11 +77,21 @@ pub fn main_vs(
     #[spirv(uniform, descriptor_set = 0, binding = 0)] camera: &Mat4,
     #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] input: &[ModelVertex],
     #[spirv(storage_buffer, descriptor_set = 3, binding = 0)] instances: &[Instance],
+    #[spirv(storage_buffer, descriptor_set = 5, binding = 0)] joints: &[Mat4],
     #[spirv(position)] out_pos: &mut Vec4,
     output: &mut ModelVertex,
 ) {
     let mut vertex = input[in_vertex_index as usize];
     let instance = instances[in_instance_index as usize];
+    // joint matrices already place skinned vertices in the world, their instance is left at the origin
+    if vertex.weights != Vec4::ZERO {
+        let skin = joints[vertex.joints.x as usize] * vertex.weights.x
+            + joints[vertex.joints.y as usize] * vertex.weights.y
+            + joints[vertex.joints.z as usize] * vertex.weights.z
+            + joints[vertex.joints.w as usize] * vertex.weights.w;
+        vertex.position = skin * vertex.position.xyz().extend(1.);
+        vertex.normal = skin * vertex.normal.xyz().extend(0.);
+    }
     vertex.position = (instance.position.xyz() + Affine3A::from_quat(instance.rotation).transform_point3(vertex.position.xyz())).extend(1.);
     vertex.normal = (Affine3A::from_quat(instance.rotation).transform_vector3(vertex.normal.xyz())).extend(0.);
 
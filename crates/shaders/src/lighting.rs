@@ -14,6 +14,7 @@ pub struct LightContext {
     light_ray: Vec3,
     light_color: Vec3,
     dampen: f32,
+    specular: f32,
 }
 
 impl LightContext {
@@ -28,8 +29,13 @@ impl LightContext {
                 .pipe(|Color([r, g, b, _])| Vec3::new(r, g, b)),
             model_vertex,
             light_source,
+            specular: 1.,
         }
     }
+    /// scales the specular highlight, surfaces default to the full one
+    pub fn with_specular(self, specular: f32) -> Self {
+        Self { specular, ..self }
+    }
     fn apply_specular(&self, light_buffer: &mut Vec3) {
         let eye_direction = self.eye_direction;
         let reflection_direction = (-self.light_ray.reflect(self.model_vertex.normal.xyz())).normalize();
@@ -40,7 +46,8 @@ impl LightContext {
                 .mul(2.0f32.powf(1.4))
                 .clamp(0., 1.)
             * 0.33
-            * self.dampen;
+            * self.dampen
+            * self.specular;
     }
     fn apply_ambient(&self, light_buffer: &mut Vec3) {
         self.pipe(
@@ -51,6 +58,7 @@ impl LightContext {
                  light_ray: _,
                  eye_direction: _,
                  dampen: _,
+                 specular: _,
              }| {
                 let light = light_color * 0.06;
                 // let light = light * dampen;
@@ -69,11 +77,14 @@ impl LightContext {
                          normal,
                          tex_coords: _,
                          padding: _,
+                         joints: _,
+                         weights: _,
                      },
                  light_source: LightSource { position: _, color: _ },
                  light_ray,
                  eye_direction: _,
                  dampen,
+                 specular: _,
              }| {
                 let diffuse_intensity = normal.xyz().dot(light_ray.normalize()).max(0.);
                 // let diffuse_intensity = diffuse_intensity * dampen;
@@ -0,0 +1,78 @@
+#[cfg(target_arch = "spirv")]
+#[allow(unused_imports)]
+use spirv_std::num_traits::Float;
+use {
+    crate::{
+        compute::{hash, invocation_index, random_vec3},
+        text::{CORNER_X, CORNER_Y},
+    },
+    glam::{Mat4, UVec3, Vec2, Vec4, Vec4Swizzles},
+    shader_types::particle::{Emitter, Particle},
+    spirv_std::spirv,
+};
+
+#[spirv(compute(threads(64)))]
+pub fn particles_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] particles: &mut [Particle],
+    #[spirv(uniform, descriptor_set = 0, binding = 1)] emitter: &Emitter,
+) {
+    let Some(index) = invocation_index(id, emitter.capacity) else {
+        return;
+    };
+    let delta_time = emitter.acceleration.w;
+    let mut particle = particles[index];
+    if (index as u32 + emitter.capacity - emitter.spawn_start) % emitter.capacity < emitter.spawn_count {
+        let mut seed = hash(index as u32 ^ hash(emitter.seed));
+        let position = emitter.origin.xyz() + random_vec3(&mut seed) * emitter.origin.w;
+        let velocity = emitter.velocity.xyz() + random_vec3(&mut seed) * emitter.velocity.w;
+        // lifetimes vary a bit so a burst doesn't die all at once
+        let lifetime = emitter.lifetime * (0.75 + random_vec3(&mut seed).x * 0.25);
+        particle = Particle {
+            position: position.extend(0.),
+            velocity: velocity.extend(lifetime),
+        };
+    } else if particle.position.w < particle.velocity.w {
+        let velocity = particle.velocity.xyz() + emitter.acceleration.xyz() * delta_time;
+        particle = Particle {
+            position: (particle.position.xyz() + velocity * delta_time).extend(particle.position.w + delta_time),
+            velocity: velocity.extend(particle.velocity.w),
+        };
+    }
+    particles[index] = particle;
+}
+
+/// one camera facing quad per instance, dead particles collapse to a point outside the view
+#[spirv(vertex)]
+pub fn particle_vs(
+    #[spirv(vertex_index)] vertex_index: u32,
+    #[spirv(instance_index)] instance_index: u32,
+    #[spirv(uniform, descriptor_set = 0, binding = 0)] camera: &Mat4,
+    #[spirv(storage_buffer, descriptor_set = 1, binding = 0)] particles: &[Particle],
+    #[spirv(uniform, descriptor_set = 1, binding = 1)] emitter: &Emitter,
+    #[spirv(position)] out_pos: &mut Vec4,
+    out_tex_coords: &mut Vec2,
+    out_color: &mut Vec4,
+) {
+    let particle = particles[instance_index as usize];
+    let corner = Vec2::new(((CORNER_X >> vertex_index) & 1) as f32, ((CORNER_Y >> vertex_index) & 1) as f32);
+    *out_tex_coords = corner;
+    if particle.position.w >= particle.velocity.w {
+        *out_pos = Vec4::ZERO;
+        *out_color = Vec4::ZERO;
+        return;
+    }
+    let progress = particle.position.w / particle.velocity.w;
+    let size = emitter.size_start + (emitter.size_end - emitter.size_start) * progress;
+    let offset = (corner - 0.5) * size;
+    let position = particle.position.xyz() + emitter.billboard_right.xyz() * offset.x - emitter.billboard_up.xyz() * offset.y;
+    *out_pos = *camera * position.extend(1.);
+    *out_color = emitter.color_start.lerp(emitter.color_end, progress);
+}
+
+/// round and soft edged, fading out towards the rim
+#[spirv(fragment)]
+pub fn particle_fs(tex_coords: Vec2, color: Vec4, output: &mut Vec4) {
+    let falloff = (1. - (tex_coords * 2. - 1.).length()).clamp(0., 1.);
+    *output = color * Vec4::new(1., 1., 1., falloff);
+}
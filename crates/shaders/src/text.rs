@@ -0,0 +1,38 @@
+use {
+    glam::{Vec2, Vec4, Vec4Swizzles},
+    spirv_std::{image::Image2d, spirv, Sampler},
+};
+
+/// corners of the two triangles making up a glyph quad, one bit per vertex
+pub(crate) const CORNER_X: u32 = 0b110010;
+pub(crate) const CORNER_Y: u32 = 0b101100;
+
+#[spirv(vertex)]
+pub fn text_vs(
+    #[spirv(vertex_index)] vertex_index: u32,
+    rect: Vec4,
+    tex_coords: Vec4,
+    color: Vec4,
+    #[spirv(position)] out_pos: &mut Vec4,
+    out_tex_coords: &mut Vec2,
+    out_color: &mut Vec4,
+) {
+    let corner = Vec2::new(((CORNER_X >> vertex_index) & 1) as f32, ((CORNER_Y >> vertex_index) & 1) as f32);
+    *out_pos = (rect.xy() + (rect.zw() - rect.xy()) * corner)
+        .extend(0.)
+        .extend(1.);
+    *out_tex_coords = tex_coords.xy() + (tex_coords.zw() - tex_coords.xy()) * corner;
+    *out_color = color;
+}
+
+/// the atlas is white, coverage lives in alpha
+#[spirv(fragment)]
+pub fn text_fs(
+    #[spirv(descriptor_set = 1, binding = 0)] atlas: &Image2d,
+    #[spirv(descriptor_set = 1, binding = 1)] sampler: &Sampler,
+    tex_coords: Vec2,
+    color: Vec4,
+    output: &mut Vec4,
+) {
+    *output = color * atlas.sample(*sampler, tex_coords);
+}